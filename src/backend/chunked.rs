@@ -0,0 +1,100 @@
+use std::io::Write;
+
+use rayon::prelude::*;
+
+use hash::Prefix;
+use settings::Settings;
+use util::{MProef, MProefRange};
+
+use super::{Backend, ListFormat};
+use super::rayon::{install, RayonBackend};
+
+/// The amount of numbers that a worker checks at once
+const CHUNK_SIZE: u64 = 1 << 14;
+
+/// The amount of chunks per thread that are buffered before they are written
+const CHUNKS_PER_THREAD: u64 = 4;
+
+/// A backend that lists numbers without any lock
+///
+/// The range is processed in rounds of a few chunks per thread. The workers
+/// buffer the matches of their chunk, after which a prefix sum over the amount
+/// of matches per chunk tells every chunk the counter of its first line. The
+/// workers then format their chunk into a block of lines, and the blocks are
+/// written in order. As a result, the output is sorted, like the output of the
+/// `SequentialBackend`.
+pub struct ChunkedBackend;
+
+impl Backend for ChunkedBackend {
+    // Redirect run_count and the searches to the RayonBackend, since they don't use locks
+    fn run_count(settings: &Settings) -> u32 { RayonBackend::run_count(settings) }
+    fn run_find_first<F: Fn(MProefRange<'_>) -> Option<u32> + Sync>(settings: &Settings, find_first: F) -> Option<u32> {
+        RayonBackend::run_find_first(settings, find_first)
+    }
+    fn run_prefix_search(settings: &Settings, prefix: &Prefix) -> Vec<u32> {
+        RayonBackend::run_prefix_search(settings, prefix)
+    }
+
+    fn run_map<R: Send, M: Fn(u32) -> R + Sync>(settings: &Settings, f: M) -> Vec<R> {
+        RayonBackend::run_map(settings, f)
+    }
+
+    fn run_list_as<W: Write + Send, F: ListFormat>(settings: &Settings, mut out: W, format: &F) {
+        // For all x: bottom <= x < top
+        //        and m_proef(x, modulo)
+        // Print the count and the number
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+
+        // Computed in `u64`, so the last chunk can end at `u32::MAX`
+        let bottom = settings.bottom as u64;
+        let top = (settings.top as u64).max(bottom);
+        let round_size = CHUNK_SIZE * CHUNKS_PER_THREAD * settings.threads.max(1) as u64;
+
+        let mut counter = 1;
+        let mut chunks = vec![];
+        let mut blocks = vec![];
+
+        install(settings, || {
+            let mut round_start = bottom;
+            while round_start < top {
+                let round_end = (round_start + round_size).min(top);
+                let chunk_count = (round_end - round_start).div_ceil(CHUNK_SIZE) as u32;
+                let chunk_range = |i: u32| {
+                    let start = round_start + i as u64 * CHUNK_SIZE;
+                    start as u32..(start + CHUNK_SIZE).min(round_end) as u32
+                };
+
+                // Buffer the matches of every chunk
+                (0..chunk_count).into_par_iter()
+                    .map(|i| m_proef.range(chunk_range(i)).collect::<Vec<u32>>())
+                    .collect_into(&mut chunks);
+
+                // The counter of the first match in every chunk
+                let firsts: Vec<u32> = chunks.iter().map(|chunk| {
+                    let first = counter;
+                    counter += chunk.len() as u32;
+                    first
+                }).collect();
+
+                // Format every chunk into a block of lines
+                (0..chunk_count).into_par_iter()
+                    .map(|i| {
+                        let chunk = &chunks[i as usize];
+                        let mut block = Vec::with_capacity(chunk.len() * 20);
+                        for (counter, x) in (firsts[i as usize]..).zip(chunk) {
+                            format.write_line(&mut block, counter, *x).unwrap();
+                        }
+                        block
+                    })
+                    .collect_into(&mut blocks);
+
+                // Only this thread writes, so the blocks can be written as a whole
+                for block in &blocks {
+                    out.write_all(block).unwrap();
+                }
+
+                round_start = round_end;
+            }
+        });
+    }
+}
@@ -0,0 +1,68 @@
+use std::any;
+use std::io::Write;
+use std::marker::PhantomData;
+
+use hash::Prefix;
+use settings::Settings;
+use lock::{Instrumented, Lock, LockKind};
+use util::{MProef, MProefRange};
+
+use rayon::prelude::*;
+
+use super::{Backend, ListFormat};
+use super::rayon::{install, par_chunks, RayonBackend};
+
+/// A backend that uses a lock with a guard-based API in `run_list`
+///
+/// The lock is selected through one of the types in `lock::kind`, e.g.
+/// `GuardedLockBackend<kind::TicketLock>`.
+pub struct GuardedLockBackend<K>(PhantomData<K>);
+
+impl<K: LockKind> Backend for GuardedLockBackend<K> {
+    // Redirect run_count and the searches to the RayonBackend, since they don't use locks
+    fn run_count(settings: &Settings) -> u32 { RayonBackend::run_count(settings) }
+    fn run_find_first<F: Fn(MProefRange<'_>) -> Option<u32> + Sync>(settings: &Settings, find_first: F) -> Option<u32> {
+        RayonBackend::run_find_first(settings, find_first)
+    }
+    fn run_prefix_search(settings: &Settings, prefix: &Prefix) -> Vec<u32> {
+        RayonBackend::run_prefix_search(settings, prefix)
+    }
+
+    fn run_map<R: Send, M: Fn(u32) -> R + Sync>(settings: &Settings, f: M) -> Vec<R> {
+        RayonBackend::run_map(settings, f)
+    }
+
+    // Implement our own version of run_list
+    fn run_list_as<W: Write + Send, F: ListFormat>(settings: &Settings, out: W, format: &F) {
+        if settings.lock_stats {
+            let lock = Instrumented::<K::Lock<_>>::new(K::Lock::new((1, out)));
+            list(settings, &lock, format);
+
+            let kind = any::type_name::<K>().rsplit("::").next().unwrap();
+            eprintln!("{}: {}", kind, lock.stats());
+        } else {
+            list(settings, &K::Lock::new((1, out)), format);
+        }
+    }
+}
+
+/// Run the list mode with the given lock around the counter and the output
+fn list<W: Write + Send, L: Lock<(u32, W)>, F: ListFormat>(settings: &Settings, lock: &L, format: &F) {
+    // For all x: bottom <= x < top
+    //        and m_proef(x, modulo)
+    // Print the count and the number
+    let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+
+    install(settings, || {
+        par_chunks(settings).for_each(|chunk| {
+            for x in m_proef.range(chunk) {
+                // A poisoned lock means another worker panicked while writing,
+                // so fail as well instead of writing after a partial line
+                let mut guard = lock.lock().unwrap();
+                let (ref mut counter, ref mut out) = *guard;
+                format.write_line(out, *counter, x).unwrap();
+                *counter += 1;
+            }
+        });
+    });
+}
@@ -4,21 +4,141 @@
 mod sequential;
 mod rayon;
 mod spin_lock;
-mod advanced_spin_lock;
+mod guarded_lock;
 mod threads;
+mod chunked;
+mod schedule;
+mod search;
 
+use std::io::{self, Write};
+
+use hash::{Prefix, Target};
+use lock::kind;
 use settings::Settings;
+use util::MProefRange;
 
 pub use self::sequential::SequentialBackend;
 pub use self::rayon::RayonBackend;
 pub use self::spin_lock::SpinLockBackend;
-pub use self::advanced_spin_lock::AdvancedSpinLockBackend;
+pub use self::guarded_lock::GuardedLockBackend;
 pub use self::threads::ThreadBackend;
+pub use self::chunked::ChunkedBackend;
+pub use self::schedule::ChunkCursor;
+pub use self::search::SearchResult;
+
+pub type AdvancedSpinLockBackend = GuardedLockBackend<kind::AdvancedSpinLock>;
+pub type TicketLockBackend = GuardedLockBackend<kind::TicketLock>;
+pub type McsLockBackend = GuardedLockBackend<kind::McsLock>;
+pub type HybridLockBackend = GuardedLockBackend<kind::HybridLock>;
+
+/// The names of the backends, as accepted by `with_backend`. Use them to
+/// select a backend through `IBAN_BACKEND`.
+pub const BACKEND_NAMES: [&str; 9] = [
+    "spin_lock",
+    "rayon",
+    "advanced_spin_lock",
+    "threads",
+    "sequential",
+    "ticket_lock",
+    "mcs_lock",
+    "hybrid_lock",
+    "chunked",
+];
+
+/// The name of the only backend whose list output is in ascending order
+pub const ORDERED_BACKEND: &str = "sequential";
+
+/// What the list mode writes for every number, after its counter
+///
+/// The assignment lists the numbers themselves (`Plain`), but the backends can
+/// list anything that is derived from them, e.g. the IBANs of the `generate`
+/// mode (see `iban::DutchIbans`).
+pub trait ListFormat: Sync {
+    /// Write the item of `x`, without the counter and the newline
+    fn write_item<W: Write>(&self, out: &mut W, x: u32) -> io::Result<()>;
+
+    /// Write the whole line of `x`
+    fn write_line<W: Write>(&self, out: &mut W, counter: u32, x: u32) -> io::Result<()> {
+        write!(out, "{} ", counter)?;
+        self.write_item(out, x)?;
+        out.write_all(b"\n")
+    }
+}
+
+/// Lists the numbers themselves, with leading zeros up to `width` digits (see
+/// `Checksum::width`)
+pub struct Plain {
+    pub width: usize,
+}
+
+impl ListFormat for Plain {
+    fn write_item<W: Write>(&self, out: &mut W, x: u32) -> io::Result<()> {
+        write!(out, "{:01$}", x, self.width)
+    }
+
+    fn write_line<W: Write>(&self, out: &mut W, counter: u32, x: u32) -> io::Result<()> {
+        writeln!(out, "{} {:02$}", counter, x, self.width)
+    }
+}
 
 /// The `Backend` trait provides functions to execute the three program modes
 /// from the `IbanCalculator` assignment.
 pub trait Backend {
+    /// Count the numbers in the range that pass the m_proef
     fn run_count(settings: &Settings) -> u32;
-    fn run_list(settings: &Settings);
-    fn run_search(settings: &Settings, hash: Box<[u8; 20]>) -> Option<u32>;
+    /// Write every number that passes the m_proef to `out`, one per line,
+    /// preceded by a counter that starts at 1
+    fn run_list<W: Write + Send>(settings: &Settings, out: W) {
+        Self::run_list_as(settings, out, &Plain { width: settings.checksum.width() })
+    }
+    /// Like `run_list`, but write the item of `format` instead of the number
+    fn run_list_as<W: Write + Send, F: ListFormat>(settings: &Settings, out: W, format: &F);
+    /// Find the number that passes the m_proef and whose sha1 equals `hash`
+    fn run_search(settings: &Settings, hash: Box<[u8; 20]>) -> Option<u32> {
+        let target = Target::new(&hash).with_width(settings.checksum.width());
+        Self::run_find_first(settings, |numbers| target.find(numbers))
+    }
+    /// Find the smallest number that passes the m_proef and that `find_first`
+    /// finds, whichever thread finds it first
+    ///
+    /// `find_first` gets the numbers of a chunk of the range that pass the
+    /// m_proef, in ascending order, and returns the first one it looks for.
+    fn run_find_first<F: Fn(MProefRange<'_>) -> Option<u32> + Sync>(settings: &Settings, find_first: F) -> Option<u32>;
+    /// Find every number that passes the m_proef and whose sha1 starts with
+    /// `prefix`, in ascending order
+    ///
+    /// Every number passes the m_proef with modulo 1, so that is how to search
+    /// without filtering.
+    fn run_prefix_search(settings: &Settings, prefix: &Prefix) -> Vec<u32>;
+    /// Compute `f` of every number in the range, whether or not it passes the
+    /// m_proef, and return the results in the order of the numbers
+    ///
+    /// This runs other work than the assignment on the threads of the backend,
+    /// e.g. the validation of the rows of a file (see `validate`).
+    fn run_map<R: Send, M: Fn(u32) -> R + Sync>(settings: &Settings, f: M) -> Vec<R>;
+}
+
+/// Code that is generic over the backend, so it can be run with a backend that
+/// is only known by name at runtime (see `with_backend`)
+pub trait BackendFn {
+    type Output;
+    fn call<T: Backend>(self) -> Self::Output;
+}
+
+/// Call `f` with the backend that has the given name
+///
+/// Returns `None` if there is no backend with that name.
+pub fn with_backend<F: BackendFn>(name: &str, f: F) -> Option<F::Output> {
+    Some(match name {
+        "spin_lock" => f.call::<SpinLockBackend>(),
+        "rayon" => f.call::<RayonBackend>(),
+        "advanced_spin_lock" => f.call::<AdvancedSpinLockBackend>(),
+        "threads" => f.call::<ThreadBackend>(),
+        "sequential" => f.call::<SequentialBackend>(),
+        "ticket_lock" => f.call::<TicketLockBackend>(),
+        "mcs_lock" => f.call::<McsLockBackend>(),
+        "hybrid_lock" => f.call::<HybridLockBackend>(),
+        "chunked" => f.call::<ChunkedBackend>(),
+        _ => return None
+    })
 }
@@ -1,20 +1,61 @@
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::Range;
+use std::sync::{Arc, Mutex, OnceLock};
 
-use rayon::{self, Configuration};
+use rayon::{Configuration, ThreadPool};
 use rayon::prelude::*;
 
+use lock::kind;
 use settings::Settings;
-use util;
+use hash::{Prefix, Target};
+use util::{MProef, MProefRange};
 
-use super::Backend;
+use super::{Backend, GuardedLockBackend, ListFormat};
+use super::search::SearchResult;
 
-pub struct RayonBackend(());
+pub struct RayonBackend;
 
-impl RayonBackend {
-    pub fn new(num_threads: usize) -> RayonBackend {
-        rayon::initialize(Configuration::new().set_num_threads(num_threads)).unwrap();
-        RayonBackend(())
-    }
+/// The amount of numbers in the chunks of `par_chunks`
+const CHUNK_SIZE: u64 = 4096;
+
+/// Run `op` on a thread pool with as many threads as specified in the settings
+///
+/// Note: rayon's global thread pool can only be configured once per process,
+/// so we keep a pool for each amount of threads instead. This way, runs with a
+/// different amount of threads (e.g. in the tests and benchmarks) use the right
+/// amount, without paying for a new pool every time.
+pub fn install<OP, R>(settings: &Settings, op: OP) -> R
+    where OP: FnOnce() -> R + Send
+{
+    static POOLS: OnceLock<Mutex<HashMap<u32, Arc<ThreadPool>>>> = OnceLock::new();
+
+    // Like `ThreadBackend`, 0 threads runs on a single one
+    let threads = settings.threads.max(1);
+    let pool = {
+        let mut pools = POOLS.get_or_init(Default::default).lock().unwrap();
+        pools.entry(threads).or_insert_with(|| {
+            let config = Configuration::new().set_num_threads(threads as usize);
+            Arc::new(ThreadPool::new(config).unwrap())
+        }).clone()
+    };
+    pool.install(op)
+}
+
+/// Split the range of the settings into chunks, that can be checked in parallel
+///
+/// Every chunk is checked by a single thread, so it can go through its numbers
+/// with an `MProefRange`, which is much faster than checking them one by one.
+pub fn par_chunks(settings: &Settings) -> impl ParallelIterator<Item=Range<u32>> {
+    // Computed in `u64`, so the last chunk can end at `u32::MAX`
+    let bottom = settings.bottom as u64;
+    let top = (settings.top as u64).max(bottom);
+    let chunk_count = (top - bottom).div_ceil(CHUNK_SIZE) as u32;
+
+    (0..chunk_count).into_par_iter().map(move |i| {
+        let start = bottom + i as u64 * CHUNK_SIZE;
+        start as u32..(start + CHUNK_SIZE).min(top) as u32
+    })
 }
 
 impl Backend for RayonBackend {
@@ -22,34 +63,67 @@ impl Backend for RayonBackend {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
         // Count the numer of element satisfying the predicate
-        let modulo = settings.modulo;
-        let range = (settings.bottom .. settings.top).into_par_iter();
-        range.filter(|&x| util::m_proef(x, modulo)).count() as u32
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        install(settings, || {
+            par_chunks(settings).map(|chunk| m_proef.range(chunk).count() as u32).sum()
+        })
+    }
+
+    fn run_list_as<W: Write + Send, F: ListFormat>(settings: &Settings, out: W, format: &F) {
+        // A `GuardedLockBackend` with the std `Mutex`, so it can print lock
+        // statistics in the same way as the other lock-based backends
+        GuardedLockBackend::<kind::StdMutex>::run_list_as(settings, out, format)
     }
 
-    fn run_list(settings: &Settings) {
+    fn run_find_first<F: Fn(MProefRange<'_>) -> Option<u32> + Sync>(settings: &Settings, find_first: F) -> Option<u32> {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
-        // Print the count and the number
-        let modulo = settings.modulo;
-        let range = (settings.bottom .. settings.top).into_par_iter();
-
-        let mutex = Mutex::new(1);
-
-        range.filter(|&x| util::m_proef(x, modulo)).for_each(|x| {
-            let mut counter = mutex.lock().unwrap();
-            println!("{} {}", *counter, x);
-            *counter += 1;
+        // Find the smallest x that find_first looks for
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        let result = SearchResult::new();
+        install(settings, || {
+            par_chunks(settings).for_each(|chunk| {
+                // Skip the chunk if a smaller number has been found
+                if result.is_settled_below(chunk.start) {
+                    return;
+                }
+                if let Some(x) = find_first(m_proef.range(chunk)) {
+                    result.publish(x);
+                }
+            })
         });
+
+        // `install` waits for every chunk, so every hit has been published
+        result.best()
     }
 
-    fn run_search(settings: &Settings, hash: Box<[u8; 20]>) -> Option<u32> {
+    fn run_prefix_search(settings: &Settings, prefix: &Prefix) -> Vec<u32> {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
-        // Find an x such that sha1(x) == hash
-        (settings.bottom .. settings.top)
-            .into_par_iter()
-            .find_any(|&x| util::m_proef(x, settings.modulo)
-                        && util::valid_hash(x, &hash))
+        // Find every x such that sha1(x) starts with prefix
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        let target = Target::with_prefix(prefix.clone()).with_width(settings.checksum.width());
+        let mut found = install(settings, || {
+            par_chunks(settings)
+                .map(|chunk| target.find_all(m_proef.range(chunk)))
+                .filter(|found| !found.is_empty())
+                .reduce_with(|mut left, right| {
+                    left.extend(right);
+                    left
+                })
+        }).unwrap_or_default();
+        found.sort_unstable();
+        found
+    }
+
+    fn run_map<R: Send, M: Fn(u32) -> R + Sync>(settings: &Settings, f: M) -> Vec<R> {
+        let f = &f;
+        let mut results = vec![];
+        install(settings, || {
+            (settings.bottom .. settings.top.max(settings.bottom)).into_par_iter()
+                .map(f)
+                .collect_into(&mut results)
+        });
+        results
     }
 }
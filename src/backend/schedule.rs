@@ -0,0 +1,47 @@
+use std::iter;
+use std::ops::Range;
+
+use sync::{AtomicU64, Ordering};
+
+/// Hands out consecutive chunks of a range to whichever thread asks first
+///
+/// A thread that is done with its chunk simply takes the next one, so no thread
+/// sits idle while others still have work. All threads move through the range
+/// together, so in search mode the thread that owns the answer doesn't start
+/// on it last.
+pub struct ChunkCursor {
+    /// The start of the next chunk, which is past `high` once all chunks are
+    /// handed out
+    next: AtomicU64,
+    high: u64,
+    chunk_size: u64,
+}
+
+impl ChunkCursor {
+    /// Creates a cursor over `low..high` that hands out chunks of `chunk_size`
+    /// numbers (the last one may be smaller)
+    pub fn new(low: u32, high: u32, chunk_size: u32) -> ChunkCursor {
+        ChunkCursor {
+            next: AtomicU64::new(low as u64),
+            // Computed in `u64`, so cursors past the end can't wrap around
+            high: high as u64,
+            chunk_size: chunk_size.max(1) as u64,
+        }
+    }
+
+    /// Takes the next chunk, or returns `None` if the whole range is handed out
+    pub fn next_chunk(&self) -> Option<Range<u32>> {
+        // Every chunk is handed out exactly once by the atomic add itself, there
+        // is no other data to synchronize with
+        let start = self.next.fetch_add(self.chunk_size, Ordering::Relaxed);
+        if start >= self.high {
+            return None;
+        }
+        Some(start as u32..(start + self.chunk_size).min(self.high) as u32)
+    }
+
+    /// Takes chunks until the whole range is handed out
+    pub fn chunks(&self) -> impl Iterator<Item=Range<u32>> + '_ {
+        iter::from_fn(move || self.next_chunk())
+    }
+}
@@ -0,0 +1,87 @@
+use std::ops::Range;
+
+use sync::{AtomicU64, Mutex, Ordering};
+
+use super::schedule::ChunkCursor;
+
+/// `SearchResult::best` when there is no hit yet, which no `u32` can be
+const NO_HIT: u64 = u64::MAX;
+
+/// The hits of a search, published by the threads that search in parallel
+///
+/// When several threads find a hit, the smallest one wins, regardless of which
+/// thread was first. This makes the result of a search deterministic, also
+/// when more than one number matches. All hits are kept, for searches that
+/// want every match instead of a single one.
+pub struct SearchResult {
+    /// The smallest hit so far, or `NO_HIT`
+    best: AtomicU64,
+    /// Every published hit, in no particular order
+    hits: Mutex<Vec<u32>>,
+}
+
+impl Default for SearchResult {
+    fn default() -> SearchResult {
+        SearchResult::new()
+    }
+}
+
+impl SearchResult {
+    pub fn new() -> SearchResult {
+        SearchResult { best: AtomicU64::new(NO_HIT), hits: Mutex::new(vec![]) }
+    }
+
+    /// Publishes a hit, and returns whether it is the smallest one so far
+    pub fn publish(&self, x: u32) -> bool {
+        // Hits are rare, so they can take a lock
+        self.hits.lock().unwrap().push(x);
+
+        // The hit itself is the only data, so `Relaxed` is enough: the final
+        // result is read after all threads are joined
+        self.best.fetch_min(x as u64, Ordering::Relaxed) > x as u64
+    }
+
+    /// Returns the smallest hit so far
+    pub fn best(&self) -> Option<u32> {
+        match self.best.load(Ordering::Relaxed) {
+            NO_HIT => None,
+            x => Some(x as u32),
+        }
+    }
+
+    /// Returns true if a hit below `x` has been found, so a thread that only
+    /// looks for the smallest hit doesn't need to check `x` or anything above
+    pub fn is_settled_below(&self, x: u32) -> bool {
+        self.best.load(Ordering::Relaxed) < x as u64
+    }
+
+    /// Returns every published hit, in ascending order
+    ///
+    /// `search` skips the numbers above a hit, so after it this only holds
+    /// the hits that were found before the smallest one was known.
+    pub fn into_hits(self) -> Vec<u32> {
+        let mut hits = self.hits.into_inner().unwrap();
+        hits.sort_unstable();
+        hits
+    }
+
+    /// Searches the chunks of the cursor until they run out, or until every
+    /// number that is left is larger than a hit
+    ///
+    /// `find_first` returns the first hit in a chunk, if any. The cursor hands
+    /// out chunks in ascending order, so once a hit is found below a chunk, the
+    /// chunk and every later one can be skipped. Every thread of a search can
+    /// call this with the same cursor.
+    pub fn search<F: Fn(Range<u32>) -> Option<u32>>(&self, cursor: &ChunkCursor, find_first: F) {
+        for chunk in cursor.chunks() {
+            // Early stop if a smaller number has been found
+            if self.is_settled_below(chunk.start) {
+                return;
+            }
+
+            if let Some(x) = find_first(chunk) {
+                self.publish(x);
+            }
+        }
+    }
+}
@@ -1,9 +1,10 @@
-use sha1::Sha1;
+use std::io::Write;
 
 use settings::Settings;
-use util;
+use hash::{Prefix, Target};
+use util::{MProef, MProefRange};
 
-use super::Backend;
+use super::{Backend, ListFormat};
 
 pub struct SequentialBackend;
 
@@ -12,34 +13,38 @@ impl Backend for SequentialBackend {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
         // Count the numer of element satisfying the predicate
-        let modulo = settings.modulo;
-        let range = settings.bottom .. settings.top;
-        range.filter(|&x| util::m_proef(x, modulo)).count() as u32
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        m_proef.range(settings.bottom .. settings.top).count() as u32
     }
 
-    fn run_list(settings: &Settings) {
+    fn run_list_as<W: Write + Send, F: ListFormat>(settings: &Settings, mut out: W, format: &F) {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
         // Print the count and the number
-        let range = settings.bottom .. settings.top;
-        let mut counter = 1;
-        for x in range {
-            if util::m_proef(x, settings.modulo) {
-                println!("{} {}", counter, x);
-
-                counter += 1;
-            }
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        for (counter, x) in (1..).zip(m_proef.range(settings.bottom .. settings.top)) {
+            format.write_line(&mut out, counter, x).unwrap();
         }
     }
 
-    fn run_search(settings: &Settings, hash: Box<[u8; 20]>) -> Option<u32> {
+    fn run_find_first<F: Fn(MProefRange<'_>) -> Option<u32> + Sync>(settings: &Settings, find_first: F) -> Option<u32> {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
-        // Find an x such that sha1(x) == hash
-        let mut sha1 = Sha1::new();
-        let mut buffer: Vec<u8> = Vec::with_capacity(9);
-        (settings.bottom .. settings.top)
-            .find(|&x| util::m_proef(x, settings.modulo)
-                    && util::valid_hash_fast(x, &hash, &mut buffer, &mut sha1))
+        // Find the first x that find_first looks for
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        find_first(m_proef.range(settings.bottom .. settings.top))
+    }
+
+    fn run_prefix_search(settings: &Settings, prefix: &Prefix) -> Vec<u32> {
+        // For all x: bottom <= x < top
+        //        and m_proef(x, modulo)
+        // Find every x such that sha1(x) starts with prefix
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        let target = Target::with_prefix(prefix.clone()).with_width(settings.checksum.width());
+        target.find_all(m_proef.range(settings.bottom .. settings.top))
+    }
+
+    fn run_map<R: Send, M: Fn(u32) -> R + Sync>(settings: &Settings, f: M) -> Vec<R> {
+        (settings.bottom .. settings.top).map(f).collect()
     }
 }
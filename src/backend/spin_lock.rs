@@ -1,42 +1,97 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::LockResult;
+
 use rayon::prelude::*;
 
-use super::Backend;
-use super::rayon::RayonBackend;
+use super::{Backend, ListFormat};
+use super::rayon::{install, par_chunks, RayonBackend};
 
-use lock::SpinLock;
+use hash::Prefix;
+use lock::{Instrumented, SpinLock};
 use settings::Settings;
-use util;
+use util::{MProef, MProefRange};
 
-pub struct SpinLockBackend(RayonBackend);
+pub struct SpinLockBackend;
 
-impl SpinLockBackend {
-    pub fn new(num_threads: usize) -> SpinLockBackend {
-        SpinLockBackend(RayonBackend::new(num_threads))
-    }
+thread_local! {
+    /// The line that a worker formats before taking the lock
+    ///
+    /// rayon 0.5 has no `for_each_init`, so the buffer lives with the worker
+    /// thread, and every line reuses its allocation.
+    static ITEM: RefCell<Vec<u8>> = RefCell::new(Vec::with_capacity(32));
 }
 
 impl Backend for SpinLockBackend {
-    // Redirect run_count and run_search to the RayonBackend, since they don't use locks
+    // Redirect run_count and the searches to the RayonBackend, since they don't use locks
     fn run_count(settings: &Settings) -> u32 { RayonBackend::run_count(settings) }
-    fn run_search(settings: &Settings, hash: Box<[u8; 20]>) -> Option<u32> {
-        RayonBackend::run_search(settings, hash)
+    fn run_find_first<F: Fn(MProefRange<'_>) -> Option<u32> + Sync>(settings: &Settings, find_first: F) -> Option<u32> {
+        RayonBackend::run_find_first(settings, find_first)
+    }
+    fn run_prefix_search(settings: &Settings, prefix: &Prefix) -> Vec<u32> {
+        RayonBackend::run_prefix_search(settings, prefix)
+    }
+
+    fn run_map<R: Send, M: Fn(u32) -> R + Sync>(settings: &Settings, f: M) -> Vec<R> {
+        RayonBackend::run_map(settings, f)
     }
 
     // Implement our own version of run_list
-    fn run_list(settings: &Settings) {
-        // For all x: bottom <= x < top
-        //        and m_proef(x, modulo)
-        // Print the count and the number
-        let modulo = settings.modulo;
-        let range = (settings.bottom .. settings.top).into_par_iter();
-
-        let mutex = SpinLock::new(1);
-
-        range.filter(|&x| util::m_proef(x, modulo)).for_each(|x| {
-            mutex.lock(|counter| {
-                println!("{} {}", counter, x);
-                *counter += 1;
-            });
-        });
+    fn run_list_as<W: Write + Send, F: ListFormat>(settings: &Settings, out: W, format: &F) {
+        if settings.lock_stats {
+            let lock = Instrumented::new(SpinLock::new((1, out)));
+            list(settings, &lock, format);
+            eprintln!("SpinLock: {}", lock.stats());
+        } else {
+            list(settings, &SpinLock::new((1, out)), format);
+        }
     }
 }
+
+/// A lock that runs a closure while holding it, like `SpinLock`
+trait ClosureLock<T>: Sync {
+    fn run<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> LockResult<R>;
+}
+
+impl<T: Send> ClosureLock<T> for SpinLock<T> {
+    fn run<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> LockResult<R> { self.lock(f) }
+}
+
+impl<T: Send> ClosureLock<T> for Instrumented<SpinLock<T>> {
+    fn run<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> LockResult<R> { self.lock(f) }
+}
+
+/// Run the list mode with the given lock around the counter and the output
+fn list<W: Write + Send, L: ClosureLock<(u32, W)>, F: ListFormat>(settings: &Settings, lock: &L, format: &F) {
+    // For all x: bottom <= x < top
+    //        and m_proef(x, modulo)
+    // Print the count and the number
+    let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+
+    install(settings, || {
+        par_chunks(settings).for_each(|chunk| ITEM.with(|item| {
+            let mut item = item.borrow_mut();
+            for x in m_proef.range(chunk) {
+                // Format the item before taking the lock, so the critical
+                // section only has to assign the next counter and write. The
+                // buffer is cleared first, in case a panic left a partial line
+                item.clear();
+                format.write_item(&mut *item, x).unwrap();
+                item.push(b'\n');
+
+                // A poisoned lock means another worker panicked in the critical
+                // section, so fail as well instead of writing after a partial line
+                let (counter, written) = lock.run(|&mut (ref mut counter, ref mut out)| {
+                    let index = *counter;
+                    *counter += 1;
+                    (index, write!(out, "{} ", index).and_then(|()| out.write_all(&item)))
+                }).unwrap();
+
+                // Report write errors outside the lock, so they don't poison it
+                if let Err(e) = written {
+                    panic!("failed to write line {}: {}", counter, e);
+                }
+            }
+        }));
+    });
+}
@@ -1,24 +1,24 @@
-use std::fmt::Debug;
-use std::sync::{mpsc, Arc};
-use std::sync::atomic::{AtomicIsize, Ordering};
+use std::io::Write;
+use std::sync::mpsc::{self, Receiver};
 use std::thread;
 
+use lock::stats::LockStats;
 use settings::Settings;
-use util;
+use hash::{Prefix, Target};
+use util::{MProef, MProefRange};
 
-use super::Backend;
+use super::{Backend, ListFormat};
+use super::schedule::ChunkCursor;
+use super::search::SearchResult;
 
+/// A backend that uses plain threads, which take chunks of the range from a
+/// shared `ChunkCursor` until the whole range is done
+///
+/// The size of the chunks is set by `Settings::chunk_size`.
 pub struct ThreadBackend;
 
-fn split_ranges(low: u32, high: u32, chunks: u32) -> impl Iterator<Item=impl Iterator<Item=u32> + Debug> {
-    let delta = (high - low) / chunks;
-    (0..chunks).map(move |i| {
-        if i == chunks - 1 {
-            low + delta * i..high
-        } else {
-            low + delta * i..low + delta * (1+i)
-        }
-    })
+fn cursor(settings: &Settings) -> ChunkCursor {
+    ChunkCursor::new(settings.bottom, settings.top, settings.chunk_size)
 }
 
 impl Backend for ThreadBackend {
@@ -26,84 +26,126 @@ impl Backend for ThreadBackend {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
         // Count the numer of element satisfying the predicate
-        let mut threads = vec![];
-
-        for range in split_ranges(settings.bottom, settings.top, settings.threads) {
-            let modulo = settings.modulo;
-
-            // Spin up another thread
-            threads.push(thread::spawn(move || {
-                range.filter(|&x| util::m_proef(x, modulo)).count() as u32
-            }));
-        }
-
-        threads.into_iter().map(|thread| thread.join().unwrap()).sum()
+        let cursor = cursor(settings);
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+
+        thread::scope(|scope| {
+            let threads: Vec<_> = (0..settings.threads.max(1)).map(|_| scope.spawn(|| {
+                cursor.chunks()
+                    .map(|chunk| m_proef.range(chunk).count() as u32)
+                    .sum::<u32>()
+            })).collect();
+
+            threads.into_iter().map(|thread| thread.join().unwrap()).sum()
+        })
     }
 
-    fn run_list(settings: &Settings) {
+    fn run_list_as<W: Write + Send, F: ListFormat>(settings: &Settings, out: W, format: &F) {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
         // Print the count and the number
-
-        let mut threads = vec![];
+        let cursor = cursor(settings);
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
         let (send, recv) = mpsc::channel();
 
-        for range in split_ranges(settings.bottom, settings.top, settings.threads) {
-            let modulo = settings.modulo;
-            let send = send.clone();
+        thread::scope(|scope| {
+            for _ in 0..settings.threads.max(1) {
+                let send = send.clone();
+                let (cursor, m_proef) = (&cursor, &m_proef);
 
-            // Spin up another thread
-            threads.push(thread::spawn(move || {
-                for x in range {
-                    if util::m_proef(x, modulo) {
+                // Spin up another thread
+                scope.spawn(move || {
+                    for x in cursor.chunks().flat_map(|chunk| m_proef.range(chunk)) {
                         send.send(x).unwrap();
                     }
-                };
-                drop(send);
-            }));
-        }
-        drop(send);
+                });
+            }
+            drop(send);
 
-        // The parent thread acts as printer thread as well
-        let mut counter = 1;
-        for x in recv {
-           println!("{} {}", counter, x);
-           counter += 1;
-        }
+            // The parent thread acts as printer thread as well
+            print(recv, out, format, settings.lock_stats);
+        });
     }
 
-    fn run_search(settings: &Settings, hash: Box<[u8; 20]>) -> Option<u32> {
+    fn run_find_first<F: Fn(MProefRange<'_>) -> Option<u32> + Sync>(settings: &Settings, find_first: F) -> Option<u32> {
         // For all x: bottom <= x < top
         //        and m_proef(x, modulo)
-        // Find an x such that sha1(x) == hash
-        let mut threads = vec![];
-        let hash = Arc::new(*hash);
-        let found = Arc::new(AtomicIsize::new(-1));
-
-        for range in split_ranges(settings.bottom, settings.top, settings.threads) {
-            let modulo = settings.modulo;
-            let hash = hash.clone();
-            let found = found.clone();
-
-            // Spin up another thread
-            threads.push(thread::spawn(move || {
-                for x in range {
-                    // Early stop if the number has been found
-                    if found.load(Ordering::Relaxed) != -1 {
-                        return;
-                    }
+        // Find the smallest x that find_first looks for
+        let cursor = cursor(settings);
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        let result = SearchResult::new();
+
+        thread::scope(|scope| {
+            for _ in 0..settings.threads.max(1) {
+                scope.spawn(|| result.search(&cursor, |chunk| find_first(m_proef.range(chunk))));
+            }
+        });
+
+        // The threads are joined, so every hit has been published
+        result.best()
+    }
 
-                    if util::m_proef(x, modulo) && util::valid_hash(x, &hash) {
-                        found.store(x as isize, Ordering::Relaxed);
-                    }
-                };
-            }))
-        };
+    fn run_prefix_search(settings: &Settings, prefix: &Prefix) -> Vec<u32> {
+        // For all x: bottom <= x < top
+        //        and m_proef(x, modulo)
+        // Find every x such that sha1(x) starts with prefix
+        let cursor = cursor(settings);
+        let m_proef = MProef::with_checksum(settings.modulo, settings.checksum);
+        let target = Target::with_prefix(prefix.clone()).with_width(settings.checksum.width());
+
+        let mut found: Vec<u32> = thread::scope(|scope| {
+            let threads: Vec<_> = (0..settings.threads.max(1)).map(|_| scope.spawn(|| {
+                cursor.chunks()
+                    .flat_map(|chunk| target.find_all(m_proef.range(chunk)))
+                    .collect::<Vec<u32>>()
+            })).collect();
+
+            threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect()
+        });
+        found.sort_unstable();
+        found
+    }
+
+    fn run_map<R: Send, M: Fn(u32) -> R + Sync>(settings: &Settings, f: M) -> Vec<R> {
+        let cursor = cursor(settings);
+
+        // Every thread keeps the results of its chunks with their start, so
+        // they can be put back in order
+        let mut chunks: Vec<(u32, Vec<R>)> = thread::scope(|scope| {
+            let threads: Vec<_> = (0..settings.threads.max(1)).map(|_| scope.spawn(|| {
+                cursor.chunks()
+                    .map(|chunk| (chunk.start, chunk.map(&f).collect()))
+                    .collect::<Vec<_>>()
+            })).collect();
+
+            threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect()
+        });
+        chunks.sort_unstable_by_key(|&(start, _)| start);
+        chunks.into_iter().flat_map(|(_, results)| results).collect()
+    }
+}
 
-        for thread in threads { thread.join().unwrap(); }
-        match found.load(Ordering::SeqCst) {
-            -1 => None,
-            x  => Some(x as u32)
+/// Write the numbers in the order in which they are received, with a counter
+fn print<W: Write, F: ListFormat>(recv: Receiver<u32>, mut out: W, format: &F, lock_stats: bool) {
+    if !lock_stats {
+        for (counter, x) in (1..).zip(recv) {
+           format.write_line(&mut out, counter, x).unwrap();
         }
+        return;
+    }
+
+    // There is no lock, but the printer waits for numbers like the workers
+    // of the other backends wait for the lock, so measure that instead
+    let stats = LockStats::new();
+    for counter in 1.. {
+        let waiting = stats.start_waiting();
+        let x = match recv.recv() {
+            Ok(x) => x,
+            Err(_) => break
+        };
+        let holding = stats.acquired(waiting, 0);
+        format.write_line(&mut out, counter, x).unwrap();
+        stats.released(holding);
     }
+    eprintln!("channel: {}", stats.summary());
 }
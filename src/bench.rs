@@ -0,0 +1,364 @@
+//! A benchmark harness to compare the backends and their locks.
+//!
+//! The benchmark runs every combination of mode, backend, amount of threads and
+//! range size a few times and keeps the median time. For each result it reports
+//! the throughput (checked numbers per second) and the speedup with respect to
+//! the same benchmark with a single thread.
+//!
+//! The list mode is the only one that takes a lock, so comparing the list results
+//! of `rayon` (std `Mutex`), `spin_lock` and `advanced_spin_lock` compares the
+//! locks. The output of the list mode is discarded, so the printing itself
+//! doesn't dominate the measurements.
+//!
+//! Results can be written as CSV and later be passed as a baseline, in which
+//! case every benchmark that got slower than the tolerance allows is flagged as
+//! a regression.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::hint;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Instant;
+
+use backend::*;
+use output::OutputSink;
+use settings::{Settings, DEFAULT_CHUNK_SIZE};
+use util::Checksum;
+
+/// The first number of every benchmarked range, taken from the TomJudge tests
+const BOTTOM: u32 = 2824374;
+
+/// The modulo used in the benchmarks, taken from the TomJudge tests
+const MODULO: u32 = 24;
+
+/// A hash that doesn't belong to any number, so search has to check the whole range
+const UNKNOWN_HASH: [u8; 20] = [0; 20];
+
+const CSV_HEADER: &str = "mode,backend,lock,threads,size,seconds,throughput,speedup";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BenchMode {
+    Count,
+    List,
+    Search,
+}
+
+impl BenchMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            BenchMode::Count => "count",
+            BenchMode::List => "list",
+            BenchMode::Search => "search",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<BenchMode> {
+        [BenchMode::Count, BenchMode::List, BenchMode::Search].iter()
+            .cloned()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+/// The lock used by a backend in list mode
+pub fn lock_name(backend: &str) -> &'static str {
+    match backend {
+        "rayon" => "mutex",
+        "spin_lock" => "spin_lock",
+        "advanced_spin_lock" => "advanced_spin_lock",
+        "threads" => "channel",
+        "ticket_lock" => "ticket_lock",
+        "mcs_lock" => "mcs_lock",
+        "hybrid_lock" => "hybrid_lock",
+        "chunked" => "lock_free",
+        _ => "none",
+    }
+}
+
+#[derive(Debug)]
+pub struct BenchConfig {
+    pub modes: Vec<BenchMode>,
+    pub backends: Vec<&'static str>,
+    pub threads: Vec<u32>,
+    pub sizes: Vec<u32>,
+    pub runs: usize,
+    pub output: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    /// The relative slowdown with respect to the baseline that is tolerated
+    pub tolerance: f64,
+}
+
+impl Default for BenchConfig {
+    fn default() -> BenchConfig {
+        // Double the threads until all cores are in use
+        let cores = thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1);
+        let mut threads = vec![1];
+        while *threads.last().unwrap() < cores {
+            let next = (threads.last().unwrap() * 2).min(cores);
+            threads.push(next);
+        }
+
+        BenchConfig {
+            modes: vec![BenchMode::Count, BenchMode::List, BenchMode::Search],
+            backends: BACKEND_NAMES.to_vec(),
+            threads,
+            sizes: vec![1_000_000, 10_000_000],
+            runs: 3,
+            output: None,
+            baseline: None,
+            tolerance: 0.1,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BenchArgsError {
+    MissingValue(String),
+    InvalidValue(String, String),
+    UnknownArgument(String),
+}
+
+impl fmt::Display for BenchArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::BenchArgsError::*;
+        match *self {
+            MissingValue(ref flag) => write!(f, "missing value for {}", flag),
+            InvalidValue(ref flag, ref value) => write!(f, "invalid value for {}: {}", flag, value),
+            UnknownArgument(ref arg) => write!(f, "unknown argument: {}", arg),
+        }
+    }
+}
+
+/// Parse the arguments that follow the `bench` subcommand
+///
+/// Lists are comma separated, e.g. `--threads 1,2,4 --sizes 1000000`.
+pub fn parse_args<I: Iterator<Item=String>>(mut args: I) -> Result<BenchConfig, BenchArgsError> {
+    use self::BenchArgsError::*;
+
+    fn list<T, F: Fn(&str) -> Option<T>>(flag: &str, value: &str, f: F) -> Result<Vec<T>, BenchArgsError> {
+        value.split(',')
+            .map(|item| f(item).ok_or_else(|| InvalidValue(flag.to_owned(), item.to_owned())))
+            .collect()
+    }
+
+    let mut config = BenchConfig::default();
+    while let Some(flag) = args.next() {
+        let value = match args.next() {
+            Some(value) => value,
+            None => return Err(MissingValue(flag))
+        };
+        match &*flag {
+            "--modes" => config.modes = list(&flag, &value, BenchMode::from_name)?,
+            "--backends" => config.backends = list(&flag, &value, |name| {
+                BACKEND_NAMES.iter().cloned().find(|&n| n == name)
+            })?,
+            "--threads" => config.threads = list(&flag, &value, |n| n.parse().ok().filter(|&n| n > 0))?,
+            "--sizes" => config.sizes = list(&flag, &value, |n| n.parse().ok())?,
+            "--runs" => config.runs = value.parse().ok().filter(|&n| n > 0)
+                .ok_or_else(|| InvalidValue(flag.clone(), value.clone()))?,
+            "--tolerance" => config.tolerance = value.parse()
+                .map_err(|_| InvalidValue(flag.clone(), value.clone()))?,
+            "--output" => config.output = Some(PathBuf::from(value)),
+            "--baseline" => config.baseline = Some(PathBuf::from(value)),
+            _ => return Err(UnknownArgument(flag))
+        }
+    }
+
+    // The speedup is computed relative to the single threaded run, which
+    // therefore needs to run first
+    config.threads.sort();
+    config.threads.dedup();
+    Ok(config)
+}
+
+/// The result of a single benchmark
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub mode: BenchMode,
+    pub backend: String,
+    pub threads: u32,
+    pub size: u32,
+    /// The median time of all runs
+    pub seconds: f64,
+    /// The speedup with respect to the run with a single thread, if available
+    pub speedup: Option<f64>,
+}
+
+impl BenchResult {
+    /// The amount of numbers checked per second
+    pub fn throughput(&self) -> f64 {
+        self.size as f64 / self.seconds
+    }
+
+    fn key(&self) -> (BenchMode, &str, u32, u32) {
+        (self.mode, &self.backend, self.threads, self.size)
+    }
+}
+
+/// Time a single run of the backend passed to `call`
+struct TimeOnce<'a> {
+    settings: &'a Settings,
+    mode: BenchMode,
+}
+
+impl<'a> BackendFn for TimeOnce<'a> {
+    type Output = f64;
+
+    fn call<T: Backend>(self) -> f64 {
+        let settings = self.settings;
+        let start = Instant::now();
+        match self.mode {
+            BenchMode::Count => { hint::black_box(T::run_count(settings)); }
+            BenchMode::List => {
+                // Unlike `io::sink()`, the discarding sink counts the written
+                // bytes, so the compiler cannot optimize the listing away
+                let mut sink = OutputSink::discard();
+                T::run_list(settings, &mut sink);
+                hint::black_box(sink.bytes_written());
+            }
+            BenchMode::Search => { hint::black_box(T::run_search(settings, Box::new(UNKNOWN_HASH))); }
+        }
+        start.elapsed().as_secs_f64()
+    }
+}
+
+/// Run a single benchmark `runs` times and return the median time
+pub fn measure(backend: &str, mode: BenchMode, threads: u32, size: u32, runs: usize) -> f64 {
+    let settings = Settings {
+        custom_lock: true,
+        bottom: BOTTOM,
+        top: BOTTOM.saturating_add(size),
+        modulo: MODULO,
+        threads,
+        chunk_size: DEFAULT_CHUNK_SIZE,
+        lock_stats: false,
+        checksum: Checksum::MProef,
+    };
+
+    let mut times: Vec<f64> = (0..runs)
+        .map(|_| with_backend(backend, TimeOnce { settings: &settings, mode }).unwrap())
+        .collect();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    times[times.len() / 2]
+}
+
+/// Run all benchmarks in the configuration, printing each result as it comes in
+pub fn run_benchmarks(config: &BenchConfig) -> Vec<BenchResult> {
+    let mut results: Vec<BenchResult> = vec![];
+    for &mode in &config.modes {
+        for &backend in &config.backends {
+            // The sequential backend ignores the amount of threads
+            let threads: &[u32] = if backend == ORDERED_BACKEND { &[1] } else { &config.threads };
+            for &size in &config.sizes {
+                for &n in threads {
+                    let seconds = measure(backend, mode, n, size, config.runs);
+                    let speedup = results.iter()
+                        .find(|r| r.key() == (mode, backend, 1, size))
+                        .map(|single| single.seconds / seconds);
+                    let result = BenchResult {
+                        mode,
+                        backend: backend.to_owned(),
+                        threads: n,
+                        size,
+                        seconds,
+                        speedup,
+                    };
+                    print_result(&result);
+                    results.push(result);
+                }
+            }
+        }
+    }
+    results
+}
+
+fn print_result(result: &BenchResult) {
+    let speedup = match result.speedup {
+        Some(speedup) => format!("{:.2}x", speedup),
+        None => "-".to_owned(),
+    };
+    println!("{:<7} {:<20} {:<20} {:>3} threads {:>10} numbers {:>10.4} s {:>14.0} /s {:>7}",
+             result.mode.name(), result.backend, lock_name(&result.backend), result.threads,
+             result.size, result.seconds, result.throughput(), speedup);
+}
+
+/// Write the results as CSV
+pub fn write_csv<W: Write>(mut out: W, results: &[BenchResult]) -> io::Result<()> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    for r in results {
+        let speedup = r.speedup.map(|s| s.to_string()).unwrap_or_default();
+        writeln!(out, "{},{},{},{},{},{},{},{}", r.mode.name(), r.backend, lock_name(&r.backend),
+                 r.threads, r.size, r.seconds, r.throughput(), speedup)?;
+    }
+    Ok(())
+}
+
+/// Read results previously written by `write_csv`
+pub fn read_csv<R: BufRead>(input: R) -> io::Result<Vec<BenchResult>> {
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid line: {}", line));
+
+    let mut results = vec![];
+    for line in input.lines().skip(1) {
+        let line = line?;
+        let fields: Vec<_> = line.split(',').collect();
+        if fields.len() != 8 {
+            return Err(invalid(&line));
+        }
+        results.push(BenchResult {
+            mode: BenchMode::from_name(fields[0]).ok_or_else(|| invalid(&line))?,
+            backend: fields[1].to_owned(),
+            threads: fields[3].parse().map_err(|_| invalid(&line))?,
+            size: fields[4].parse().map_err(|_| invalid(&line))?,
+            seconds: fields[5].parse().map_err(|_| invalid(&line))?,
+            speedup: fields[7].parse().ok(),
+        });
+    }
+    Ok(results)
+}
+
+/// A benchmark that got slower with respect to the baseline
+#[derive(Debug)]
+pub struct Regression {
+    pub result: BenchResult,
+    pub baseline_seconds: f64,
+}
+
+/// Compare the results against a baseline and return every benchmark that
+/// is slower than the tolerance allows
+///
+/// Benchmarks that are missing from the baseline are ignored.
+pub fn find_regressions(baseline: &[BenchResult], results: &[BenchResult], tolerance: f64) -> Vec<Regression> {
+    let baseline: HashMap<_, _> = baseline.iter().map(|r| (r.key(), r.seconds)).collect();
+    results.iter().filter_map(|r| {
+        baseline.get(&r.key())
+            .filter(|&&old| r.seconds > old * (1.0 + tolerance))
+            .map(|&old| Regression { result: r.clone(), baseline_seconds: old })
+    }).collect()
+}
+
+/// Run the benchmarks, write the results and compare them against the baseline
+///
+/// Returns false if there were regressions.
+pub fn run(config: &BenchConfig) -> io::Result<bool> {
+    let results = run_benchmarks(config);
+
+    if let Some(ref path) = config.output {
+        write_csv(File::create(path)?, &results)?;
+    }
+
+    let baseline = match config.baseline {
+        Some(ref path) => read_csv(BufReader::new(File::open(path)?))?,
+        None => return Ok(true)
+    };
+
+    let regressions = find_regressions(&baseline, &results, config.tolerance);
+    for regression in &regressions {
+        let r = &regression.result;
+        println!("REGRESSION {} {} {} threads {} numbers: {:.4} s (baseline {:.4} s)",
+                 r.mode.name(), r.backend, r.threads, r.size, r.seconds, regression.baseline_seconds);
+    }
+    println!("{} regressions against the baseline", regressions.len());
+    Ok(regressions.is_empty())
+}
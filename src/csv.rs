@@ -0,0 +1,45 @@
+//! Just enough CSV for the files that the IBAN tools read.
+//!
+//! Fields are separated by a delimiter, and may be quoted with `"` when they
+//! contain the delimiter, with `""` for a quote inside a quoted field. Fields
+//! don't span lines.
+
+use std::borrow::Cow;
+
+/// A quoted field that isn't closed before the end of the line
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnclosedQuote;
+
+/// Splits a line into its fields, without the quotes
+pub fn split(line: &str, delimiter: char) -> Result<Vec<String>, UnclosedQuote> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            _ if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(UnclosedQuote);
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// Quotes the field if it holds the delimiter or a quote
+pub fn quote(field: &str, delimiter: char) -> Cow<'_, str> {
+    if field.contains(delimiter) || field.contains('"') {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
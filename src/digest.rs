@@ -0,0 +1,225 @@
+//! Decoding of the digests that search mode looks for.
+//!
+//! A digest can be passed as:
+//!
+//! - hex, in upper or lower case, optionally starting with `0x` and with its
+//!   bytes separated by colons or spaces (e.g. `c7:36:ca:…`)
+//! - base64 or base64url, with or without the `=` padding
+//! - `file:PATH`, a file that holds the 20 raw bytes of the digest
+//!
+//! The encoding is guessed from the input, which can be overruled by starting
+//! it with `hex:`, `base64:` or `base64url:`. Errors say which character or
+//! length was wrong, instead of only that the digest is invalid.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use hash::Prefix;
+
+/// The amount of bytes in a sha1 digest
+const DIGEST_LEN: usize = 20;
+
+/// The amount of base64 characters that encode a digest, without padding
+const BASE64_LEN: usize = 27;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Hex,
+    Base64,
+    Base64Url,
+    /// The raw bytes in a file
+    Binary,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Encoding::Hex => "hex",
+            Encoding::Base64 => "base64",
+            Encoding::Base64Url => "base64url",
+            Encoding::Binary => "binary",
+        })
+    }
+}
+
+impl Encoding {
+    /// The unit of `DigestError::InvalidLength`
+    fn unit(self) -> &'static str {
+        match self {
+            Encoding::Hex => "hex digits",
+            Encoding::Base64 | Encoding::Base64Url => "characters",
+            Encoding::Binary => "bytes",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DigestError {
+    /// A character that doesn't belong to the encoding, with its position in
+    /// the encoded digest, counted in characters from 0
+    InvalidCharacter { encoding: Encoding, character: char, position: usize },
+    /// The digest has `length` hex digits, characters or bytes, instead of `expected`
+    InvalidLength { encoding: Encoding, length: usize, expected: usize },
+    /// A hash prefix needs 1 to 40 hex digits, but has this amount
+    InvalidPrefixLength(usize),
+    /// The file with a binary digest can't be read
+    Unreadable(PathBuf, io::ErrorKind),
+}
+
+impl fmt::Display for DigestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DigestError::InvalidCharacter { encoding, character, position } =>
+                write!(f, "invalid {} character {:?} at position {}", encoding, character, position),
+            DigestError::InvalidLength { encoding, length, expected } =>
+                write!(f, "expected {} {}, got {}", expected, encoding.unit(), length),
+            DigestError::InvalidPrefixLength(n) =>
+                write!(f, "a hash prefix needs 1 to 40 hex digits, got {}", n),
+            DigestError::Unreadable(ref path, kind) =>
+                write!(f, "cannot read {}: {}", path.display(), kind),
+        }
+    }
+}
+
+/// Decodes a digest in any of the supported encodings
+pub fn parse(s: &str) -> Result<Box<[u8; 20]>, DigestError> {
+    let (encoding, encoded) = split_encoding(s);
+    match encoding {
+        Some(Encoding::Binary) => read_binary(encoded),
+        Some(encoding) => decode(encoding, encoded),
+        None => decode(guess_encoding(s), s),
+    }
+}
+
+/// Decodes a hex digest, see `hex_digits`
+pub fn parse_hex(s: &str) -> Result<Box<[u8; 20]>, DigestError> {
+    decode(Encoding::Hex, s)
+}
+
+/// Decodes a hex hash prefix, which may start with `hex:` like a digest
+pub fn parse_prefix(s: &str) -> Result<Prefix, DigestError> {
+    Prefix::from_hex(s.strip_prefix("hex:").unwrap_or(s))
+}
+
+/// Splits off the explicit encoding at the start of the input, if any
+fn split_encoding(s: &str) -> (Option<Encoding>, &str) {
+    let encoding = match s.find(':').map(|i| &s[..i]) {
+        Some("hex") => Encoding::Hex,
+        Some("base64") => Encoding::Base64,
+        Some("base64url") => Encoding::Base64Url,
+        Some("file") => Encoding::Binary,
+        // E.g. the first byte of a hex digest with colons
+        _ => return (None, s),
+    };
+    (Some(encoding), &s[s.find(':').unwrap() + 1..])
+}
+
+/// Guesses the encoding of input without an explicit one
+///
+/// Hex is the default, so errors about other input are about hex, unless it
+/// has the length of base64.
+fn guess_encoding(s: &str) -> Encoding {
+    let unpadded = s.trim_end_matches('=');
+    if s.starts_with("0x") || s.starts_with("0X") || s.contains(':') || s.contains(' ') {
+        Encoding::Hex
+    } else if unpadded.chars().count() == BASE64_LEN {
+        if s.contains('-') || s.contains('_') { Encoding::Base64Url } else { Encoding::Base64 }
+    } else {
+        Encoding::Hex
+    }
+}
+
+fn decode(encoding: Encoding, s: &str) -> Result<Box<[u8; 20]>, DigestError> {
+    let mut digest = [0; DIGEST_LEN];
+    match encoding {
+        Encoding::Hex => {
+            let digits = hex_digits(s)?;
+            if digits.len() != 2 * DIGEST_LEN {
+                return Err(DigestError::InvalidLength { encoding, length: digits.len(), expected: 2 * DIGEST_LEN });
+            }
+            for (byte, pair) in digest.iter_mut().zip(digits.chunks_exact(2)) {
+                *byte = pair[0] << 4 | pair[1];
+            }
+        }
+        Encoding::Base64 | Encoding::Base64Url => decode_base64(encoding, s, &mut digest)?,
+        Encoding::Binary => unreachable!("binary digests are read from a file"),
+    }
+    Ok(Box::new(digest))
+}
+
+/// Returns the values of the hex digits, skipping a leading `0x` and the
+/// colons and spaces between the digits
+pub fn hex_digits(s: &str) -> Result<Vec<u8>, DigestError> {
+    let skip = if s.starts_with("0x") || s.starts_with("0X") { 2 } else { 0 };
+
+    let mut digits = Vec::with_capacity(2 * DIGEST_LEN);
+    for (position, character) in s.chars().enumerate().skip(skip) {
+        match character {
+            ':' | ' ' => {}
+            _ => match character.to_digit(16) {
+                Some(digit) => digits.push(digit as u8),
+                None => return Err(DigestError::InvalidCharacter { encoding: Encoding::Hex, character, position }),
+            }
+        }
+    }
+    Ok(digits)
+}
+
+/// The value of a base64 or base64url character
+fn base64_value(encoding: Encoding, c: char) -> Option<u32> {
+    Some(match c {
+        'A'..='Z' => c as u32 - 'A' as u32,
+        'a'..='z' => c as u32 - 'a' as u32 + 26,
+        '0'..='9' => c as u32 - '0' as u32 + 52,
+        '+' if encoding == Encoding::Base64 => 62,
+        '/' if encoding == Encoding::Base64 => 63,
+        '-' if encoding == Encoding::Base64Url => 62,
+        '_' if encoding == Encoding::Base64Url => 63,
+        _ => return None,
+    })
+}
+
+fn decode_base64(encoding: Encoding, s: &str, digest: &mut [u8; 20]) -> Result<(), DigestError> {
+    // 27 characters hold 162 bits, so a digest has a single `=` of padding
+    let unpadded = s.strip_suffix('=').unwrap_or(s);
+
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    let mut length = 0;
+    for (position, character) in unpadded.chars().enumerate() {
+        let value = match base64_value(encoding, character) {
+            Some(value) => value,
+            None => return Err(DigestError::InvalidCharacter { encoding, character, position }),
+        };
+
+        length += 1;
+        // Only the bits that aren't part of a byte yet are kept
+        bits = (bits << 6 | value) & 0x3FFF;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            if let Some(byte) = digest.get_mut(length * 6 / 8 - 1) {
+                *byte = (bits >> bit_count) as u8;
+            }
+        }
+    }
+
+    if length != BASE64_LEN {
+        return Err(DigestError::InvalidLength { encoding, length, expected: BASE64_LEN });
+    }
+    Ok(())
+}
+
+fn read_binary(path: &str) -> Result<Box<[u8; 20]>, DigestError> {
+    let path = PathBuf::from(path);
+    let bytes = fs::read(&path).map_err(|e| DigestError::Unreadable(path, e.kind()))?;
+    if bytes.len() != DIGEST_LEN {
+        return Err(DigestError::InvalidLength { encoding: Encoding::Binary, length: bytes.len(), expected: DIGEST_LEN });
+    }
+
+    let mut digest = [0; DIGEST_LEN];
+    digest.copy_from_slice(&bytes);
+    Ok(Box::new(digest))
+}
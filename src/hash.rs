@@ -0,0 +1,364 @@
+//! An allocation-free SHA-1 engine for checking candidate numbers.
+//!
+//! A candidate is at most 10 decimal digits, so its message always fits in a
+//! single padded block and hashing it is a single call of the compression
+//! function. Instead of going through a general SHA-1 implementation, the
+//! digits are formatted into a block on the stack, and `LANES` candidates are
+//! hashed side by side, with every step of the compression written as a loop
+//! over the lanes, which the compiler turns into SIMD instructions. On x86-64
+//! the lanes are compiled for AVX2 and AVX-512 as well, and `Target` picks the
+//! fastest version that the CPU supports at runtime.
+//!
+//! Consecutive candidates share their leading digits, so the first words of
+//! their blocks are equal. The rounds that only depend on those words are run
+//! once for the whole batch, and only the remaining rounds are run per lane.
+//!
+//! A `Target` is either a whole digest or a `Prefix` of one, which only
+//! compares the leading hex digits of the digests. It can hash the candidates
+//! with leading zeros up to a width, like the 9 digits of a BSN.
+
+use std::ops::Range;
+
+use digest::{self, DigestError};
+
+/// The amount of candidates that are hashed at once
+pub const LANES: usize = 16;
+
+/// The initial state of SHA-1
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+/// The decimal representation of every number below 100, for formatting two
+/// digits at a time
+const DIGIT_PAIRS: &[u8; 200] = b"\
+    0001020304050607080910111213141516171819\
+    2021222324252627282930313233343536373839\
+    4041424344454647484950515253545556575859\
+    6061626364656667686970717273747576777879\
+    8081828384858687888990919293949596979899";
+
+/// Formats `x` into the end of `buffer`, and returns the digits
+#[inline]
+pub fn format(mut x: u32, buffer: &mut [u8; 10]) -> &[u8] {
+    let mut start = buffer.len();
+    while x >= 100 {
+        let pair = (x % 100) as usize * 2;
+        x /= 100;
+        start -= 2;
+        buffer[start..start + 2].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+    }
+    if x >= 10 {
+        let pair = x as usize * 2;
+        start -= 2;
+        buffer[start..start + 2].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+    } else {
+        start -= 1;
+        buffer[start] = b'0' + x as u8;
+    }
+    &buffer[start..]
+}
+
+/// The padded block of the decimal representation of `x`, with leading zeros
+/// up to `width` digits, as big-endian words
+#[inline]
+fn block(x: u32, width: usize) -> [u32; 16] {
+    // `format` only writes the end of the buffer, so the rest are the zeros
+    let mut digits = [b'0'; 10];
+    let len = format(x, &mut digits).len().max(width);
+    let digits = &digits[digits.len() - len..];
+
+    let mut bytes = [0; 64];
+    bytes[..digits.len()].copy_from_slice(digits);
+    bytes[digits.len()] = 0x80;
+
+    let mut words = [0; 16];
+    for (word, bytes) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    // The length of the message in bits
+    words[15] = digits.len() as u32 * 8;
+    words
+}
+
+/// Runs round `i` of the compression function on a single state
+#[inline(always)]
+fn round(state: &mut [u32; 5], i: usize, w: u32) {
+    let [a, b, c, d, e] = *state;
+    let (f, k) = match i / 20 {
+        0 => ((b & c) | (!b & d), 0x5A827999),
+        1 => (b ^ c ^ d, 0x6ED9EBA1),
+        2 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+        _ => (b ^ c ^ d, 0xCA62C1D6),
+    };
+    let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w);
+    *state = [temp, a, b.rotate_left(30), c, d];
+}
+
+/// Hashes the decimal representation of `x`, with leading zeros up to `width`
+/// digits
+pub fn digest(x: u32, width: usize) -> [u32; 5] {
+    let mut w = block(x, width);
+    let mut state = INITIAL_STATE;
+    for i in 0..80 {
+        if i >= 16 {
+            w[i % 16] = (w[(i + 13) % 16] ^ w[(i + 8) % 16] ^ w[(i + 2) % 16] ^ w[i % 16]).rotate_left(1);
+        }
+        round(&mut state, i, w[i % 16]);
+    }
+    for (s, h) in state.iter_mut().zip(&INITIAL_STATE) {
+        *s = s.wrapping_add(*h);
+    }
+    state
+}
+
+/// A state or a message word for every lane
+type Lanes = [u32; LANES];
+
+/// Runs rounds `rounds` on every lane, where `f` is the round function
+///
+/// The round function is the same for a group of 20 rounds, so every group
+/// gets its own copy of this loop without a branch in it.
+#[inline(always)]
+fn lane_rounds<F>(state: &mut [Lanes; 5], w: &[Lanes; 80], rounds: Range<usize>, k: u32, f: F)
+    where F: Fn(u32, u32, u32) -> u32
+{
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for w in &w[rounds] {
+        for l in 0..LANES {
+            let temp = a[l].rotate_left(5)
+                .wrapping_add(f(b[l], c[l], d[l]))
+                .wrapping_add(e[l])
+                .wrapping_add(k)
+                .wrapping_add(w[l]);
+            e[l] = d[l];
+            d[l] = c[l];
+            c[l] = b[l].rotate_left(30);
+            b[l] = a[l];
+            a[l] = temp;
+        }
+    }
+    *state = [a, b, c, d, e];
+}
+
+/// Hashes the blocks of `LANES` candidates at once, and returns the digests
+/// per word
+///
+/// This is compiled once for every instruction set in `digest_lanes_for_cpu`.
+#[inline(always)]
+fn digest_lanes(blocks: &[[u32; 16]; LANES]) -> [Lanes; 5] {
+    // The rounds before the first word that differs between the lanes are the
+    // same for every lane, so they are run only once
+    let shared = (0..16).find(|&i| blocks.iter().any(|block| block[i] != blocks[0][i])).unwrap_or(16);
+    let mut prefix = INITIAL_STATE;
+    for (i, &w) in blocks[0][..shared].iter().enumerate() {
+        round(&mut prefix, i, w);
+    }
+
+    let mut state = [[0; LANES]; 5];
+    for (lanes, &s) in state.iter_mut().zip(&prefix) {
+        *lanes = [s; LANES];
+    }
+
+    // The whole message schedule, expanded for every lane up front
+    let mut w = [[0; LANES]; 80];
+    for (i, lanes) in w[..16].iter_mut().enumerate() {
+        for (l, block) in blocks.iter().enumerate() {
+            lanes[l] = block[i];
+        }
+    }
+    for i in 16..80 {
+        let (w3, w8, w14, w16) = (w[i - 3], w[i - 8], w[i - 14], w[i - 16]);
+        for (l, w) in w[i].iter_mut().enumerate() {
+            *w = (w3[l] ^ w8[l] ^ w14[l] ^ w16[l]).rotate_left(1);
+        }
+    }
+
+    lane_rounds(&mut state, &w, shared..20, 0x5A827999, |b, c, d| (b & c) | (!b & d));
+    lane_rounds(&mut state, &w, 20..40, 0x6ED9EBA1, |b, c, d| b ^ c ^ d);
+    lane_rounds(&mut state, &w, 40..60, 0x8F1BBCDC, |b, c, d| (b & c) | (b & d) | (c & d));
+    lane_rounds(&mut state, &w, 60..80, 0xCA62C1D6, |b, c, d| b ^ c ^ d);
+
+    for (lanes, h) in state.iter_mut().zip(&INITIAL_STATE) {
+        for s in lanes.iter_mut() {
+            *s = s.wrapping_add(*h);
+        }
+    }
+    state
+}
+
+/// `digest_lanes`, compiled for the instruction sets that x86-64 CPUs may have
+/// on top of the baseline SSE2, which has no rotate and only 4 lanes
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use super::{digest_lanes, Lanes, LANES};
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn digest_lanes_avx2(blocks: &[[u32; 16]; LANES]) -> [Lanes; 5] {
+        digest_lanes(blocks)
+    }
+
+    #[target_feature(enable = "avx512f,avx512vl")]
+    pub unsafe fn digest_lanes_avx512(blocks: &[[u32; 16]; LANES]) -> [Lanes; 5] {
+        digest_lanes(blocks)
+    }
+}
+
+/// Returns the fastest version of `digest_lanes` that the CPU supports
+fn digest_lanes_for_cpu() -> fn(&[[u32; 16]; LANES]) -> [Lanes; 5] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // Safe, because the CPU supports the features the functions are compiled for
+        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512vl") {
+            return |blocks| unsafe { x86_64::digest_lanes_avx512(blocks) };
+        }
+        if is_x86_feature_detected!("avx2") {
+            return |blocks| unsafe { x86_64::digest_lanes_avx2(blocks) };
+        }
+    }
+    digest_lanes
+}
+
+/// The start of a digest, of any amount of hex digits up to the full 40
+///
+/// Some sources only keep the first few characters of a digest. Such a prefix
+/// matches many digests, so a search for it can have more than one result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Prefix {
+    /// The digits of the prefix, as big-endian words of the digest
+    words: [u32; 5],
+    /// The bits of the words that are part of the prefix
+    masks: [u32; 5],
+    nibbles: usize,
+}
+
+impl Prefix {
+    /// A prefix that is the whole digest
+    pub fn from_digest(hash: &[u8; 20]) -> Prefix {
+        let mut words = [0; 5];
+        for (word, bytes) in words.iter_mut().zip(hash.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        Prefix { words, masks: [!0; 5], nibbles: 40 }
+    }
+
+    /// Parses 1 to 40 hex digits, which may be an odd amount
+    ///
+    /// Like a full hex digest (see `digest::parse`), the digits may start with
+    /// `0x` and be separated by colons or spaces.
+    pub fn from_hex(hex: &str) -> Result<Prefix, DigestError> {
+        let digits = digest::hex_digits(hex)?;
+        if digits.is_empty() || digits.len() > 40 {
+            return Err(DigestError::InvalidPrefixLength(digits.len()));
+        }
+
+        let mut prefix = Prefix { words: [0; 5], masks: [0; 5], nibbles: digits.len() };
+        for (i, &digit) in digits.iter().enumerate() {
+            let shift = (7 - i % 8) * 4;
+            prefix.words[i / 8] |= (digit as u32) << shift;
+            prefix.masks[i / 8] |= 0xF << shift;
+        }
+        Ok(prefix)
+    }
+
+    /// The amount of hex digits in the prefix
+    pub fn nibbles(&self) -> usize {
+        self.nibbles
+    }
+
+    /// Returns true if word `i` of a digest agrees with the prefix
+    #[inline]
+    fn matches_word(&self, i: usize, word: u32) -> bool {
+        word & self.masks[i] == self.words[i]
+    }
+}
+
+/// A digest, or the prefix of one, to search for
+#[derive(Clone, Debug)]
+pub struct Target {
+    prefix: Prefix,
+    /// The least amount of digits that the candidates are hashed with
+    width: usize,
+    digest_lanes: fn(&[[u32; 16]; LANES]) -> [Lanes; 5],
+}
+
+impl Target {
+    pub fn new(hash: &[u8; 20]) -> Target {
+        Target::with_prefix(Prefix::from_digest(hash))
+    }
+
+    pub fn with_prefix(prefix: Prefix) -> Target {
+        Target { prefix, width: 1, digest_lanes: digest_lanes_for_cpu() }
+    }
+
+    /// Hash the candidates with leading zeros up to `width` digits, e.g. 9 for
+    /// BSNs (see `Checksum::width`)
+    ///
+    /// Panics if the width is more than 10, the digits of a `u32`.
+    pub fn with_width(self, width: usize) -> Target {
+        assert!(width <= 10, "a u32 has at most 10 digits");
+        Target { width, ..self }
+    }
+
+    /// Returns true if the sha1 of the decimal representation of `x` starts
+    /// with the target
+    pub fn matches(&self, x: u32) -> bool {
+        let digest = digest(x, self.width);
+        (0..5).all(|i| self.prefix.matches_word(i, digest[i]))
+    }
+
+    /// Returns the first candidate whose sha1 starts with the target
+    pub fn find<I: IntoIterator<Item=u32>>(&self, candidates: I) -> Option<u32> {
+        let mut found = None;
+        self.scan(candidates, |x| {
+            found = Some(x);
+            false
+        });
+        found
+    }
+
+    /// Returns every candidate whose sha1 starts with the target, in the order
+    /// of the candidates
+    pub fn find_all<I: IntoIterator<Item=u32>>(&self, candidates: I) -> Vec<u32> {
+        let mut found = vec![];
+        self.scan(candidates, |x| {
+            found.push(x);
+            true
+        });
+        found
+    }
+
+    /// Passes every match to `on_match`, until it returns false
+    fn scan<I: IntoIterator<Item=u32>, F: FnMut(u32) -> bool>(&self, candidates: I, mut on_match: F) {
+        let mut candidates = candidates.into_iter();
+        let mut batch = [0; LANES];
+        let mut blocks = [[0; 16]; LANES];
+
+        loop {
+            let mut len = 0;
+            for x in candidates.by_ref().take(LANES) {
+                batch[len] = x;
+                blocks[len] = block(x, self.width);
+                len += 1;
+            }
+            if len == 0 {
+                return;
+            }
+            // Fill the unused lanes of the last batch with a copy, so they
+            // don't break up the shared words
+            for l in len..LANES {
+                blocks[l] = blocks[0];
+            }
+
+            let digests = (self.digest_lanes)(&blocks);
+
+            // Almost every candidate already differs in the first word, so
+            // the rest of the digest is only compared if that one matches
+            for l in 0..len {
+                if self.prefix.matches_word(0, digests[0][l])
+                    && (1..5).all(|i| self.prefix.matches_word(i, digests[i][l]))
+                    && !on_match(batch[l]) {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! The BIC and the name of a bank, by the bank identifier in its IBANs.
+//!
+//! The directory is read from CSV with the columns `country,bank,bic,name`,
+//! where the bank is the bank identifier of the IBAN (see `Country::bank`):
+//! the bank code for NL (`ABNA`) and the Bankleitzahl for DE (`37040044`). A
+//! directory is bundled with the crate, in `data/banks.csv`, and an updated one
+//! can be loaded from a file in the same format.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use csv;
+
+use super::countries;
+use super::Iban;
+
+/// The directory that is compiled into the crate
+const BUNDLED: &str = include_str!("../../data/banks.csv");
+
+/// The header that starts the CSV, after the comments
+const HEADER: &str = "country,bank,bic,name";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DirectoryError {
+    /// The file with the directory can't be read
+    Unreadable(PathBuf, io::ErrorKind),
+    /// The first line that isn't a comment isn't `HEADER`
+    MissingHeader,
+    /// A line, counted from 1, doesn't have 4 columns
+    InvalidColumns { line: usize, columns: usize },
+    /// A line, counted from 1, has a quoted field that isn't closed
+    UnclosedQuote { line: usize },
+    UnknownCountry { line: usize, country: String },
+    /// A bank identifier that doesn't fit the IBAN format of the country
+    InvalidBank { line: usize, bank: String },
+    /// A BIC that doesn't have 8 or 11 letters and digits
+    InvalidBic { line: usize, bic: String },
+}
+
+impl fmt::Display for DirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DirectoryError::Unreadable(ref path, kind) => write!(f, "cannot read {}: {}", path.display(), kind),
+            DirectoryError::MissingHeader => write!(f, "expected the header {:?}", HEADER),
+            DirectoryError::InvalidColumns { line, columns } =>
+                write!(f, "expected 4 columns on line {}, got {}", line, columns),
+            DirectoryError::UnclosedQuote { line } => write!(f, "unclosed quote on line {}", line),
+            DirectoryError::UnknownCountry { line, ref country } =>
+                write!(f, "unknown country {:?} on line {}", country, line),
+            DirectoryError::InvalidBank { line, ref bank } => write!(f, "invalid bank {:?} on line {}", bank, line),
+            DirectoryError::InvalidBic { line, ref bic } => write!(f, "invalid BIC {:?} on line {}", bic, line),
+        }
+    }
+}
+
+/// A bank in the directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bank {
+    pub country: &'static str,
+    /// The bank identifier in the IBAN
+    pub bank: String,
+    pub bic: String,
+    pub name: String,
+}
+
+/// The banks that we know, by country and bank identifier
+#[derive(Debug, Default)]
+pub struct BankDirectory {
+    banks: HashMap<(&'static str, String), Bank>,
+}
+
+impl BankDirectory {
+    /// The directory in `data/banks.csv`
+    pub fn bundled() -> BankDirectory {
+        BankDirectory::parse(BUNDLED).expect("the bundled bank directory is valid")
+    }
+
+    /// Reads a directory from a CSV file
+    pub fn load(path: &Path) -> Result<BankDirectory, DirectoryError> {
+        let csv = fs::read_to_string(path).map_err(|e| DirectoryError::Unreadable(path.to_owned(), e.kind()))?;
+        BankDirectory::parse(&csv)
+    }
+
+    /// Parses a directory from CSV, see the module docs
+    ///
+    /// Empty lines and lines that start with `#` are skipped. A bank that is
+    /// listed twice keeps its last entry.
+    pub fn parse(csv: &str) -> Result<BankDirectory, DirectoryError> {
+        let mut lines = csv.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end()))
+            .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#'));
+        if lines.next().map(|(_, header)| header) != Some(HEADER) {
+            return Err(DirectoryError::MissingHeader);
+        }
+
+        let mut directory = BankDirectory::default();
+        for (line, fields) in lines {
+            let fields = csv::split(fields, ',').map_err(|_| DirectoryError::UnclosedQuote { line })?;
+            let bank = parse_bank(line, fields)?;
+            directory.insert(bank);
+        }
+        Ok(directory)
+    }
+
+    pub fn insert(&mut self, bank: Bank) {
+        self.banks.insert((bank.country, bank.bank.clone()), bank);
+    }
+
+    /// Returns the bank with the bank identifier in the country
+    pub fn get(&self, country: &str, bank: &str) -> Option<&Bank> {
+        // Countries that we don't know can't be in the directory
+        let country = countries::country(country)?.code;
+        self.banks.get(&(country, bank.to_owned()))
+    }
+
+    /// Returns the bank of the IBAN
+    pub fn lookup(&self, iban: &Iban) -> Option<&Bank> {
+        self.get(iban.country(), iban.bank())
+    }
+
+    pub fn len(&self) -> usize {
+        self.banks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.banks.is_empty()
+    }
+}
+
+fn parse_bank(line: usize, fields: Vec<String>) -> Result<Bank, DirectoryError> {
+    let columns = fields.len();
+    let mut fields = fields.into_iter();
+    let (country, bank, bic, name) = match (fields.next(), fields.next(), fields.next(), fields.next(), fields.next()) {
+        (Some(country), Some(bank), Some(bic), Some(name), None) => (country, bank, bic, name),
+        _ => return Err(DirectoryError::InvalidColumns { line, columns }),
+    };
+
+    let country = match countries::country(&country) {
+        Some(country) => country,
+        None => return Err(DirectoryError::UnknownCountry { line, country }),
+    };
+    let fits = bank.chars().count() == country.bank.len()
+        && bank.chars().enumerate().all(|(i, c)| country.bban_kind(country.bank.start + i).unwrap().accepts(c));
+    if !fits {
+        return Err(DirectoryError::InvalidBank { line, bank });
+    }
+    if !(bic.len() == 8 || bic.len() == 11) || !bic.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase()) {
+        return Err(DirectoryError::InvalidBic { line, bic });
+    }
+
+    Ok(Bank { country: country.code, bank, bic, name })
+}
@@ -0,0 +1,113 @@
+//! The IBAN formats of the countries that we know, from the IBAN registry.
+
+use std::ops::Range;
+
+/// The kind of characters in a part of a BBAN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharKind {
+    /// `n`: digits
+    Digits,
+    /// `a`: uppercase letters
+    Letters,
+    /// `c`: digits and uppercase letters
+    Alphanumeric,
+}
+
+impl CharKind {
+    pub fn accepts(self, c: char) -> bool {
+        match self {
+            CharKind::Digits => c.is_ascii_digit(),
+            CharKind::Letters => c.is_ascii_uppercase(),
+            CharKind::Alphanumeric => c.is_ascii_digit() || c.is_ascii_uppercase(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CharKind::Digits => "a digit",
+            CharKind::Letters => "an uppercase letter",
+            CharKind::Alphanumeric => "a digit or an uppercase letter",
+        }
+    }
+}
+
+/// The IBAN format of a country
+#[derive(Debug, PartialEq, Eq)]
+pub struct Country {
+    /// The ISO 3166 code, which starts the IBAN
+    pub code: &'static str,
+    /// The length of the whole IBAN in electronic format
+    pub length: usize,
+    /// The positions of the bank identifier in the BBAN
+    pub bank: Range<usize>,
+    /// The parts of the BBAN, which follows the check digits
+    pub bban: &'static [(usize, CharKind)],
+}
+
+impl Country {
+    /// The kind of character that is expected at `position` in the BBAN
+    pub fn bban_kind(&self, position: usize) -> Option<CharKind> {
+        let mut start = 0;
+        for &(len, kind) in self.bban {
+            if position < start + len {
+                return Some(kind);
+            }
+            start += len;
+        }
+        None
+    }
+}
+
+use self::CharKind::{Alphanumeric as C, Digits as N, Letters as A};
+
+/// Shorthand for the `Country` entries, with the bank identifier and the BBAN
+/// parts written like the registry does: `4!a10!n` becomes `[(4, A), (10, N)]`
+macro_rules! countries {
+    ($($code:literal $length:literal $bank:expr, [$(($len:literal, $kind:ident)),*],)*) => {
+        &[$(Country { code: $code, length: $length, bank: $bank, bban: &[$(($len, $kind)),*] }),*]
+    }
+}
+
+/// Every country that we know, sorted by code
+pub static COUNTRIES: &[Country] = countries! {
+    "AD" 24 0..4, [(4, N), (4, N), (12, C)],
+    "AT" 20 0..5, [(5, N), (11, N)],
+    "BE" 16 0..3, [(3, N), (7, N), (2, N)],
+    "BG" 22 0..4, [(4, A), (4, N), (2, N), (8, C)],
+    "CH" 21 0..5, [(5, N), (12, C)],
+    "CY" 28 0..3, [(3, N), (5, N), (16, C)],
+    "CZ" 24 0..4, [(4, N), (6, N), (10, N)],
+    "DE" 22 0..8, [(8, N), (10, N)],
+    "DK" 18 0..4, [(4, N), (9, N), (1, N)],
+    "EE" 20 0..2, [(2, N), (2, N), (11, N), (1, N)],
+    "ES" 24 0..4, [(4, N), (4, N), (1, N), (1, N), (10, N)],
+    "FI" 18 0..3, [(3, N), (11, N)],
+    "FR" 27 0..5, [(5, N), (5, N), (11, C), (2, N)],
+    "GB" 22 0..4, [(4, A), (6, N), (8, N)],
+    "GR" 27 0..3, [(3, N), (4, N), (16, C)],
+    "HR" 21 0..7, [(7, N), (10, N)],
+    "HU" 28 0..3, [(3, N), (4, N), (1, N), (15, N), (1, N)],
+    "IE" 22 0..4, [(4, A), (6, N), (8, N)],
+    "IS" 26 0..2, [(4, N), (2, N), (6, N), (10, N)],
+    "IT" 27 1..6, [(1, A), (5, N), (5, N), (12, C)],
+    "LI" 21 0..5, [(5, N), (12, C)],
+    "LT" 20 0..5, [(5, N), (11, N)],
+    "LU" 20 0..3, [(3, N), (13, C)],
+    "LV" 21 0..4, [(4, A), (13, C)],
+    "MC" 27 0..5, [(5, N), (5, N), (11, C), (2, N)],
+    "MT" 31 0..4, [(4, A), (5, N), (18, C)],
+    "NL" 18 0..4, [(4, A), (10, N)],
+    "NO" 15 0..4, [(4, N), (6, N), (1, N)],
+    "PL" 28 0..8, [(8, N), (16, N)],
+    "PT" 25 0..4, [(4, N), (4, N), (11, N), (2, N)],
+    "RO" 24 0..4, [(4, A), (16, C)],
+    "SE" 24 0..3, [(3, N), (16, N), (1, N)],
+    "SI" 19 0..5, [(5, N), (8, N), (2, N)],
+    "SK" 24 0..4, [(4, N), (6, N), (10, N)],
+    "SM" 27 1..6, [(1, A), (5, N), (5, N), (12, C)],
+};
+
+/// Returns the format of the country with the given code
+pub fn country(code: &str) -> Option<&'static Country> {
+    COUNTRIES.binary_search_by(|country| country.code.cmp(code)).ok().map(|i| &COUNTRIES[i])
+}
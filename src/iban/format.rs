@@ -0,0 +1,56 @@
+//! The ways an IBAN is written.
+//!
+//! The electronic format is uppercase without spaces (`NL91ABNA0417164300`),
+//! which is what `validate` expects. The print format splits it in groups of
+//! four characters (`NL91 ABNA 0417 1643 00`). IBANs that people type or paste
+//! are normalized into the electronic format first.
+
+/// The size of the groups of the print format
+const GROUP: usize = 4;
+
+/// The prefix that IBANs are often pasted with, e.g. `IBAN: NL91…`
+const PREFIX: &str = "IBAN";
+
+/// Turns an IBAN as people write it into the electronic format
+///
+/// Removes all whitespace, including non-breaking spaces, and an `IBAN`
+/// prefix with an optional `:` after it (`IBAN: `, `IBAN : `), and turns
+/// lowercase letters into uppercase. Anything else is kept, so that `validate`
+/// can report it.
+pub fn normalize(iban: &str) -> String {
+    let iban = iban.trim();
+    let iban = match iban.get(..PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(PREFIX) => {
+            let rest = iban[PREFIX.len()..].trim_start();
+            rest.strip_prefix(':').unwrap_or(rest)
+        }
+        _ => iban,
+    };
+    iban.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+/// Returns the IBAN in electronic format in print format
+pub fn print_format(iban: &str) -> String {
+    group(iban.chars())
+}
+
+/// Returns the IBAN in electronic format in print format, with every character
+/// but the country, the check digits and the last four replaced by `*`
+///
+/// This keeps enough of the IBAN to recognize it in a log, e.g. `NL91 **** ****
+/// **43 00`.
+pub fn mask(iban: &str) -> String {
+    let length = iban.chars().count();
+    group(iban.chars().enumerate().map(|(i, c)| if i < GROUP || i + GROUP >= length { c } else { '*' }))
+}
+
+fn group<I: Iterator<Item=char>>(chars: I) -> String {
+    let mut grouped = String::new();
+    for (i, c) in chars.enumerate() {
+        if i > 0 && i % GROUP == 0 {
+            grouped.push(' ');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
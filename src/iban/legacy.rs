@@ -0,0 +1,198 @@
+//! Conversion of the Dutch account numbers from before the IBAN into IBANs.
+//!
+//! There are two kinds of account numbers:
+//!
+//! - bank accounts of 9 or 10 digits, which pass the elfproef (see
+//!   `iban::passes_elfproef`). The number doesn't say which bank it is of, so
+//!   that is looked up in a `BankRanges` table. There is no table of the real
+//!   ranges bundled, so it has to be loaded with `BankRanges::load`; without
+//!   one, only giro accounts can be converted.
+//! - giro accounts of the Postbank, of at most 7 digits, which don't pass the
+//!   elfproef. The Postbank is part of ING now, so these belong to `INGB`.
+//!
+//! Either way, the account is padded with zeros to the 10 digits of a Dutch
+//! BBAN, and gets the check digits of `DutchIbans`. Accounts that could be of
+//! more than one bank, or of none, are reported instead of guessed.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::{passes_elfproef, DutchIbans, Iban};
+
+/// The bank code of the former Postbank giro accounts
+const GIRO_BANK: &str = "INGB";
+
+/// The most digits that a giro account has
+const GIRO_DIGITS: usize = 7;
+
+/// The header that starts the CSV of a `BankRanges`, after the comments
+const HEADER: &str = "first,last,bank";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LegacyError {
+    /// A character that isn't a digit, a space or a dot, with its position
+    /// counted in characters from 0
+    InvalidCharacter { character: char, position: usize },
+    /// No digits, 8 digits or more than 10, without leading zeros
+    InvalidLength(usize),
+    /// A bank account that doesn't pass the elfproef
+    FailsElfproef(u64),
+    /// A bank account, while the table has no ranges, e.g. because none was
+    /// loaded
+    NoRanges(u64),
+    /// A bank account that isn't in any range of the table
+    UnknownBank(u64),
+    /// A bank account in the ranges of several banks
+    Ambiguous { account: u64, banks: Vec<String> },
+}
+
+impl fmt::Display for LegacyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LegacyError::InvalidCharacter { character, position } =>
+                write!(f, "invalid character {:?} at position {}", character, position),
+            LegacyError::InvalidLength(n) =>
+                write!(f, "expected a giro account of 1 to 7 digits or a bank account of 9 or 10, got {} digits", n),
+            LegacyError::FailsElfproef(account) => write!(f, "the account {} does not pass the elfproef", account),
+            LegacyError::NoRanges(account) =>
+                write!(f, "no table of bank ranges is loaded to find the bank of the account {}", account),
+            LegacyError::UnknownBank(account) => write!(f, "no bank is known for the account {}", account),
+            LegacyError::Ambiguous { account, ref banks } =>
+                write!(f, "the account {} could be of {}", account, banks.join(" or ")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TableError {
+    /// The file with the table can't be read
+    Unreadable(PathBuf, io::ErrorKind),
+    /// The first line that isn't a comment isn't `HEADER`
+    MissingHeader,
+    /// A line, counted from 1, doesn't have 3 columns
+    InvalidColumns { line: usize, columns: usize },
+    /// A line, counted from 1, with a bound that isn't an account, or with the
+    /// last account before the first
+    InvalidRange { line: usize },
+    /// A line, counted from 1, with a bank code that isn't four letters
+    InvalidBank { line: usize, bank: String },
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TableError::Unreadable(ref path, kind) => write!(f, "cannot read {}: {}", path.display(), kind),
+            TableError::MissingHeader => write!(f, "expected the header {:?}", HEADER),
+            TableError::InvalidColumns { line, columns } =>
+                write!(f, "expected 3 columns on line {}, got {}", line, columns),
+            TableError::InvalidRange { line } => write!(f, "invalid range on line {}", line),
+            TableError::InvalidBank { line, ref bank } => write!(f, "invalid bank {:?} on line {}", bank, line),
+        }
+    }
+}
+
+/// The banks of ranges of bank accounts
+///
+/// It is read from CSV with the columns `first,last,bank`, the first and the
+/// last account of a range (both included) and the bank code. Ranges of
+/// different banks may overlap, which makes the accounts in both ambiguous.
+///
+/// The default table has no ranges, so it only converts giro accounts.
+#[derive(Debug, Default)]
+pub struct BankRanges {
+    ranges: Vec<(u64, u64, DutchIbans)>,
+}
+
+impl BankRanges {
+    /// Reads a table from a CSV file
+    pub fn load(path: &Path) -> Result<BankRanges, TableError> {
+        let csv = fs::read_to_string(path).map_err(|e| TableError::Unreadable(path.to_owned(), e.kind()))?;
+        BankRanges::parse(&csv)
+    }
+
+    /// Parses a table from CSV, skipping empty lines and lines that start with `#`
+    pub fn parse(csv: &str) -> Result<BankRanges, TableError> {
+        let mut lines = csv.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end()))
+            .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#'));
+        if lines.next().map(|(_, header)| header) != Some(HEADER) {
+            return Err(TableError::MissingHeader);
+        }
+
+        let mut table = BankRanges::default();
+        for (line, fields) in lines {
+            // Accounts and bank codes never need quotes
+            let fields: Vec<_> = fields.split(',').collect();
+            let (first, last, bank) = match fields[..] {
+                [first, last, bank] => (first, last, bank),
+                _ => return Err(TableError::InvalidColumns { line, columns: fields.len() }),
+            };
+            let (first, last) = match (first.parse(), last.parse()) {
+                (Ok(first), Ok(last)) if first <= last => (first, last),
+                _ => return Err(TableError::InvalidRange { line }),
+            };
+            let bank = DutchIbans::new(bank).map_err(|_| TableError::InvalidBank { line, bank: bank.to_owned() })?;
+            table.insert(first, last, bank);
+        }
+        Ok(table)
+    }
+
+    /// Adds the range from `first` to `last`, both included
+    pub fn insert(&mut self, first: u64, last: u64, bank: DutchIbans) {
+        self.ranges.push((first, last, bank));
+    }
+
+    /// Returns the banks of the ranges that hold the account, without duplicates
+    pub fn banks(&self, account: u64) -> Vec<&DutchIbans> {
+        let mut banks: Vec<&DutchIbans> = vec![];
+        for &(first, last, ref bank) in &self.ranges {
+            if first <= account && account <= last && !banks.contains(&bank) {
+                banks.push(bank);
+            }
+        }
+        banks
+    }
+
+    /// Converts an account number from before the IBAN into an IBAN
+    ///
+    /// The number may be written with spaces and dots between its digits, like
+    /// `41.71.64.300`, and with leading zeros.
+    pub fn convert(&self, account: &str) -> Result<Iban, LegacyError> {
+        let mut digits = String::with_capacity(10);
+        for (position, character) in account.trim().chars().enumerate() {
+            match character {
+                '0'..='9' => digits.push(character),
+                ' ' | '.' => {}
+                _ => return Err(LegacyError::InvalidCharacter { character, position }),
+            }
+        }
+
+        let significant = digits.trim_start_matches('0');
+        let length = significant.len();
+        if !(1..=GIRO_DIGITS).contains(&length) && length != 9 && length != 10 {
+            return Err(LegacyError::InvalidLength(length));
+        }
+        // At most 10 digits, which always fit
+        let number = significant.parse().unwrap();
+
+        if length <= GIRO_DIGITS {
+            let giro = DutchIbans::new(GIRO_BANK).unwrap();
+            return Ok(Iban(giro.iban(number)));
+        }
+        if !passes_elfproef(significant) {
+            return Err(LegacyError::FailsElfproef(number));
+        }
+        match *self.banks(number) {
+            [] if self.ranges.is_empty() => Err(LegacyError::NoRanges(number)),
+            [] => Err(LegacyError::UnknownBank(number)),
+            [bank] => Ok(Iban(bank.iban(number))),
+            ref banks => Err(LegacyError::Ambiguous {
+                account: number,
+                banks: banks.iter().map(|bank| bank.bank().to_owned()).collect(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! IBAN check digits and validation.
+//!
+//! An IBAN is a country code, two check digits and a BBAN, the national account
+//! number, whose format depends on the country (see `countries`). The check
+//! digits make the whole IBAN pass the mod-97 test: with the first four
+//! characters moved to the end, and every letter replaced by a number from 10
+//! (A) to 35 (Z), the IBAN read as one big number leaves 1 modulo 97.
+//!
+//! Dutch BBANs are a bank code of four letters and a 10 digit account number,
+//! which passes the elfproef, i.e. `util::m_proef(account, 11)`. The `generate`
+//! mode lists the IBANs of every such account of a bank with `DutchIbans`.
+//!
+//! The functions here take IBANs in electronic format, see `format`. The `Iban`
+//! type is a valid IBAN, which can be parsed from the way people write them.
+
+pub mod banks;
+pub mod countries;
+pub mod format;
+pub mod legacy;
+pub mod suggest;
+
+use std::fmt;
+use std::str::FromStr;
+use std::io::{self, Write};
+
+use backend::ListFormat;
+use util;
+
+use self::countries::CharKind;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IbanError {
+    /// A country that is not in `countries::COUNTRIES`
+    UnknownCountry(String),
+    /// An IBAN of `length` characters, where the country has `expected`
+    InvalidLength { country: &'static str, length: usize, expected: usize },
+    /// A character that can't be part of an IBAN, with its position counted
+    /// in characters from 0
+    InvalidCharacter { character: char, position: usize },
+    /// A character at `position` of the BBAN that doesn't fit the format of
+    /// the country
+    InvalidBban { country: &'static str, position: usize, expected: CharKind },
+    /// An IBAN that doesn't pass the mod-97 test
+    InvalidCheckDigits,
+}
+
+impl fmt::Display for IbanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IbanError::UnknownCountry(ref code) => write!(f, "unknown country: {}", code),
+            IbanError::InvalidLength { country, length, expected } =>
+                write!(f, "an IBAN of {} has {} characters, got {}", country, expected, length),
+            IbanError::InvalidCharacter { character, position } =>
+                write!(f, "invalid character {:?} at position {}", character, position),
+            IbanError::InvalidBban { country, position, expected } =>
+                write!(f, "expected {} at position {} of the BBAN of {}", expected.name(), position, country),
+            IbanError::InvalidCheckDigits => write!(f, "wrong check digits"),
+        }
+    }
+}
+
+/// Returns the remainder modulo 97 of the characters read as one number, with
+/// every letter replaced by a number from 10 (A) to 35 (Z)
+pub fn mod97(s: &str) -> Result<u32, IbanError> {
+    let mut rest = 0;
+    for (position, character) in s.chars().enumerate() {
+        rest = match character {
+            '0'..='9' => (rest * 10 + character as u32 - '0' as u32) % 97,
+            'A'..='Z' => (rest * 100 + character as u32 - 'A' as u32 + 10) % 97,
+            _ => return Err(IbanError::InvalidCharacter { character, position }),
+        };
+    }
+    Ok(rest)
+}
+
+/// Checks that the BBAN has the length and the format of the country
+pub fn check_bban(country: &str, bban: &str) -> Result<&'static countries::Country, IbanError> {
+    let country = countries::country(country).ok_or_else(|| IbanError::UnknownCountry(country.to_owned()))?;
+
+    let length = bban.chars().count();
+    if length != country.length - 4 {
+        return Err(IbanError::InvalidLength { country: country.code, length: length + 4, expected: country.length });
+    }
+    for (position, c) in bban.chars().enumerate() {
+        let expected = country.bban_kind(position).unwrap();
+        if !expected.accepts(c) {
+            return Err(IbanError::InvalidBban { country: country.code, position, expected });
+        }
+    }
+    Ok(country)
+}
+
+/// Computes the check digits of the IBAN with the given country and BBAN
+pub fn check_digits(country: &str, bban: &str) -> Result<u8, IbanError> {
+    let country = check_bban(country, bban)?;
+
+    // The check digits are computed as if they were 00, which then leaves 98
+    // minus the check digits
+    let rest = (mod97(bban)? as u64 * 1_000_000 + mod97(country.code)? as u64 * 100) % 97;
+    Ok(98 - rest as u8)
+}
+
+/// Returns the IBAN, in electronic format, with the given country and BBAN
+pub fn iban(country: &str, bban: &str) -> Result<String, IbanError> {
+    let check_digits = check_digits(country, bban)?;
+    Ok(format!("{}{:02}{}", country, check_digits, bban))
+}
+
+/// Checks that an IBAN in electronic format (no spaces, uppercase) is valid
+pub fn validate(iban: &str) -> Result<(), IbanError> {
+    if let Some((position, character)) = iban.chars().enumerate().find(|&(_, c)| !c.is_ascii_alphanumeric()) {
+        return Err(IbanError::InvalidCharacter { character, position });
+    }
+
+    // Only ASCII is left, so every byte is a character
+    let code = &iban[..iban.len().min(2)];
+    let country = countries::country(code).ok_or_else(|| IbanError::UnknownCountry(code.to_owned()))?;
+    if iban.len() != country.length {
+        return Err(IbanError::InvalidLength { country: country.code, length: iban.len(), expected: country.length });
+    }
+    if let Some(position) = (2..4).find(|&i| !iban.as_bytes()[i].is_ascii_digit()) {
+        return Err(IbanError::InvalidCharacter { character: iban.as_bytes()[position] as char, position });
+    }
+
+    let (start, bban) = iban.split_at(4);
+    check_bban(country.code, bban)?;
+
+    // The mod-97 test reads the BBAN, then the country and the check digits
+    let rest = (mod97(bban)? as u64 * 1_000_000 + mod97(start)? as u64) % 97;
+    if rest != 1 {
+        return Err(IbanError::InvalidCheckDigits);
+    }
+    Ok(())
+}
+
+/// Returns true if the IBAN in electronic format is valid
+pub fn is_valid(iban: &str) -> bool {
+    validate(iban).is_ok()
+}
+
+/// A valid IBAN
+///
+/// It is parsed with `normalize` and `validate`, so `" iban nl91 abna 0417
+/// 1643 00"` is accepted. It is displayed in electronic format, or in print
+/// format with `{:#}`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Iban(String);
+
+impl Iban {
+    /// The IBAN in electronic format
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn country(&self) -> &str {
+        &self.0[..2]
+    }
+
+    pub fn check_digits(&self) -> u8 {
+        self.0[2..4].parse().unwrap()
+    }
+
+    pub fn bban(&self) -> &str {
+        &self.0[4..]
+    }
+
+    /// The bank identifier in the BBAN, e.g. `ABNA` for NL or the BLZ for DE
+    pub fn bank(&self) -> &str {
+        let country = countries::country(self.country()).unwrap();
+        &self.bban()[country.bank.clone()]
+    }
+
+    /// The IBAN in print format, e.g. `NL91 ABNA 0417 1643 00`
+    pub fn print_format(&self) -> String {
+        format::print_format(&self.0)
+    }
+
+    /// The IBAN in print format for logs, e.g. `NL91 **** **** **43 00`, see
+    /// `format::mask`
+    pub fn masked(&self) -> String {
+        format::mask(&self.0)
+    }
+}
+
+impl FromStr for Iban {
+    type Err = IbanError;
+
+    fn from_str(s: &str) -> Result<Iban, IbanError> {
+        let iban = format::normalize(s);
+        validate(&iban)?;
+        Ok(Iban(iban))
+    }
+}
+
+impl fmt::Display for Iban {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(&self.print_format())
+        } else {
+            f.write_str(&self.0)
+        }
+    }
+}
+
+/// Returns true if the 10 digit account of a Dutch BBAN passes the elfproef
+///
+/// This is `util::m_proef_digits(account, 11)`, so it also works for the
+/// accounts that don't fit a `u32`.
+pub fn passes_elfproef(account: &str) -> bool {
+    util::m_proef_digits(account, 11)
+}
+
+/// The Dutch IBANs of a single bank, by account number
+///
+/// Computing the check digits of many accounts of the same bank only needs
+/// the remainder of the bank code once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DutchIbans {
+    bank: String,
+    /// The remainder modulo 97 of the bank code
+    bank_rest: u64,
+}
+
+impl DutchIbans {
+    /// `bank` is the code of four letters, e.g. `ABNA`
+    pub fn new(bank: &str) -> Result<DutchIbans, IbanError> {
+        // Any valid account shows whether the bank code is valid
+        check_bban("NL", &format!("{}0000000000", bank))?;
+        Ok(DutchIbans { bank: bank.to_owned(), bank_rest: mod97(bank)? as u64 })
+    }
+
+    /// The bank code of four letters
+    pub fn bank(&self) -> &str {
+        &self.bank
+    }
+
+    /// `account` has at most 10 digits
+    pub fn check_digits(&self, account: u64) -> u8 {
+        // The BBAN is the bank code and the 10 digit account, followed by NL
+        // (2321) and the check digits as 00
+        let rest = (self.bank_rest * 10_000_000_000 + account) % 97;
+        98 - ((rest * 1_000_000 + 232100) % 97) as u8
+    }
+
+    /// Returns the IBAN of the account, in electronic format
+    pub fn iban(&self, account: u64) -> String {
+        format!("NL{:02}{}{:010}", self.check_digits(account), self.bank, account)
+    }
+}
+
+/// Lists the IBAN of every account, for the `generate` mode
+impl ListFormat for DutchIbans {
+    fn write_item<W: Write>(&self, out: &mut W, account: u32) -> io::Result<()> {
+        write!(out, "NL{:02}{}{:010}", self.check_digits(account as u64), self.bank, account)
+    }
+}
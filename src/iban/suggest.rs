@@ -0,0 +1,119 @@
+//! Suggestions for an IBAN with a typo.
+//!
+//! The candidates are every IBAN that is one typo away from the input: one
+//! character replaced by another, or two adjacent characters swapped. Only the
+//! candidates that pass `validate`, and for NL the elfproef on the account, are
+//! kept. They are ranked by how likely the typo is, see `Edit::rank`.
+
+use std::fmt;
+
+use super::{passes_elfproef, validate};
+
+/// The characters that can be typed instead of another one
+const ALPHABET: &[u8; 36] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Pairs of characters that look alike, and are mixed up when an IBAN is
+/// copied by hand
+const LOOK_ALIKES: &[(char, char)] = &[
+    ('0', 'O'), ('0', 'D'), ('0', 'Q'), ('1', 'I'), ('1', 'L'), ('1', '7'),
+    ('2', 'Z'), ('5', 'S'), ('6', 'G'), ('8', 'B'), ('3', '8'), ('U', 'V'),
+];
+
+/// The typo that turns a suggestion into the input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    /// The characters at `position` and `position + 1` were swapped
+    Transposition { position: usize },
+    /// `typed` was typed at `position`, instead of `intended`
+    Substitution { position: usize, typed: char, intended: char },
+}
+
+impl Edit {
+    pub fn position(self) -> usize {
+        match self {
+            Edit::Transposition { position } | Edit::Substitution { position, .. } => position,
+        }
+    }
+
+    /// How likely the typo is, from 0 (most likely) to 3
+    ///
+    /// Swapped characters are the most common typo, followed by characters
+    /// that look alike, and digits that are next to each other on a keyboard.
+    pub fn rank(self) -> u8 {
+        match self {
+            Edit::Transposition { .. } => 0,
+            Edit::Substitution { typed, intended, .. } => {
+                if LOOK_ALIKES.contains(&(typed, intended)) || LOOK_ALIKES.contains(&(intended, typed)) {
+                    1
+                } else if typed.is_ascii_digit() && intended.is_ascii_digit()
+                    && (typed as u8).abs_diff(intended as u8) == 1 {
+                    2
+                } else {
+                    3
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Edit::Transposition { position } =>
+                write!(f, "swapped positions {} and {}", position, position + 1),
+            Edit::Substitution { position, typed, intended } =>
+                write!(f, "{} instead of {} at position {}", typed, intended, position),
+        }
+    }
+}
+
+/// A valid IBAN that the input may have been meant to be
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    /// The IBAN in electronic format
+    pub iban: String,
+    pub edit: Edit,
+}
+
+/// Returns the valid IBANs that are one typo away from the IBAN in electronic
+/// format, the most likely ones first
+///
+/// The input itself is never suggested, also when it is valid.
+pub fn suggest(iban: &str) -> Vec<Suggestion> {
+    let typed: Vec<char> = iban.chars().collect();
+    let mut suggestions = vec![];
+    let mut check = |candidate: &[char], edit| {
+        let candidate: String = candidate.iter().collect();
+        if is_plausible(&candidate) {
+            suggestions.push(Suggestion { iban: candidate, edit });
+        }
+    };
+
+    let mut candidate = typed.clone();
+    for position in 0..typed.len() {
+        for &intended in ALPHABET.iter() {
+            let intended = intended as char;
+            if intended != typed[position] {
+                candidate[position] = intended;
+                check(&candidate, Edit::Substitution { position, typed: typed[position], intended });
+            }
+        }
+        candidate[position] = typed[position];
+    }
+    for position in 1..typed.len() {
+        if typed[position - 1] != typed[position] {
+            candidate.swap(position - 1, position);
+            check(&candidate, Edit::Transposition { position: position - 1 });
+            candidate.swap(position - 1, position);
+        }
+    }
+
+    suggestions.sort_by_key(|s| (s.edit.rank(), s.edit.position()));
+    suggestions
+}
+
+/// Returns true if the IBAN is valid, and for NL, the account passes the
+/// elfproef
+fn is_plausible(iban: &str) -> bool {
+    validate(iban).is_ok() && (!iban.starts_with("NL") || passes_elfproef(&iban[8..]))
+}
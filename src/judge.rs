@@ -0,0 +1,199 @@
+//! A replacement for `TomJudge.exe` that runs on any platform.
+//!
+//! A fixture directory contains pairs of files: `name.in` holds a single input
+//! line, as it would be passed on stdin, and `name.ans` the expected output.
+//! Every fixture is run against every backend by spawning the current executable
+//! with `IBAN_BACKEND` set, just like DomJudge would run it.
+//!
+//! Count and search outputs need to match exactly. List (and generate) outputs need to be
+//! numbered `1, 2, ...` in order, but the numbers themselves may be printed in
+//! any order, except for the sequential backend, which must print them ascending.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use backend::{BACKEND_NAMES, ORDERED_BACKEND};
+
+/// The maximum amount of differences reported for a failed run
+const MAX_DIFFS: usize = 10;
+
+pub struct Fixture {
+    pub name: String,
+    pub input: String,
+    pub expected: String,
+}
+
+impl Fixture {
+    /// True if the input line runs the program in list or generate mode,
+    /// whose lines may be printed in any order
+    fn is_list(&self) -> bool {
+        matches!(self.input.split_whitespace().nth(5), Some("1") | Some("3"))
+    }
+}
+
+pub enum Verdict {
+    Correct,
+    Incorrect(Vec<String>),
+    Error(String),
+}
+
+/// Load all fixtures from the given directory, sorted by name
+pub fn load_fixtures(dir: &Path) -> io::Result<Vec<Fixture>> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "in"))
+        .collect();
+    inputs.sort();
+
+    inputs.into_iter().map(|path| {
+        Ok(Fixture {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            input: read_file(&path)?,
+            expected: read_file(&path.with_extension("ans"))?,
+        })
+    }).collect()
+}
+
+fn read_file(path: &Path) -> io::Result<String> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Run the fixture against the given backend, returning the verdict and the
+/// time it took the program to run
+pub fn run_fixture(exe: &Path, backend: &str, fixture: &Fixture) -> (Verdict, Duration) {
+    let start = Instant::now();
+    let output = Command::new(exe)
+        .env("IBAN_BACKEND", backend)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.take().unwrap().write_all(fixture.input.as_bytes())?;
+            child.wait_with_output()
+        });
+    let elapsed = start.elapsed();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => return (Verdict::Error(e.to_string()), elapsed)
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        return (Verdict::Error(format!("{}: {}", output.status, stderr.trim())), elapsed);
+    }
+
+    let actual = String::from_utf8_lossy(&output.stdout);
+    let diffs = if fixture.is_list() && backend != ORDERED_BACKEND {
+        diff_unordered_list(&fixture.expected, &actual)
+    } else {
+        diff_exact(&fixture.expected, &actual)
+    };
+
+    if diffs.is_empty() {
+        (Verdict::Correct, elapsed)
+    } else {
+        (Verdict::Incorrect(diffs), elapsed)
+    }
+}
+
+/// Compare the outputs line by line
+pub fn diff_exact(expected: &str, actual: &str) -> Vec<String> {
+    let expected: Vec<_> = expected.lines().map(str::trim_end).collect();
+    let actual: Vec<_> = actual.lines().map(str::trim_end).collect();
+
+    let mut diffs = vec![];
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => continue,
+            (Some(e), Some(a)) => diffs.push(format!("line {}: expected `{}`, got `{}`", i + 1, e, a)),
+            (Some(e), None) => diffs.push(format!("line {}: expected `{}`, got nothing", i + 1, e)),
+            (None, Some(a)) => diffs.push(format!("line {}: unexpected `{}`", i + 1, a)),
+            (None, None) => unreachable!()
+        }
+    }
+
+    diffs.truncate(MAX_DIFFS);
+    diffs
+}
+
+/// Compare two list outputs, ignoring the order in which the numbers appear
+///
+/// The counters in `actual` still need to be sequential.
+pub fn diff_unordered_list(expected: &str, actual: &str) -> Vec<String> {
+    let mut diffs = vec![];
+    let mut actual_numbers = vec![];
+    for (i, line) in actual.lines().enumerate() {
+        let mut parts = line.split_whitespace();
+        let counter = parts.next();
+        if counter != Some(&*(i + 1).to_string()) {
+            diffs.push(format!("line {}: expected counter {}, got `{}`", i + 1, i + 1, line));
+        }
+        actual_numbers.push(parts.next().unwrap_or("").to_owned());
+    }
+
+    let mut expected_numbers: Vec<_> = expected.lines()
+        .map(|line| line.split_whitespace().nth(1).unwrap_or("").to_owned())
+        .collect();
+    expected_numbers.sort();
+    actual_numbers.sort();
+
+    // Walk both sorted lists to find the numbers that are missing or unexpected
+    let (mut e, mut a) = (0, 0);
+    while e < expected_numbers.len() || a < actual_numbers.len() {
+        match (expected_numbers.get(e), actual_numbers.get(a)) {
+            (Some(x), Some(y)) if x == y => { e += 1; a += 1; }
+            (Some(x), Some(y)) if x < y => { diffs.push(format!("missing `{}`", x)); e += 1; }
+            (Some(x), None) => { diffs.push(format!("missing `{}`", x)); e += 1; }
+            (_, Some(y)) => { diffs.push(format!("unexpected `{}`", y)); a += 1; }
+            (None, None) => unreachable!()
+        }
+    }
+
+    diffs.truncate(MAX_DIFFS);
+    diffs
+}
+
+/// Run all fixtures in the directory against all backends and print a report
+///
+/// Returns true if every run was correct.
+pub fn run(dir: &Path) -> bool {
+    let fixtures = match load_fixtures(dir) {
+        Ok(fixtures) => fixtures,
+        Err(e) => {
+            println!("could not load fixtures from {}: {}", dir.display(), e);
+            return false;
+        }
+    };
+    let exe = env::current_exe().unwrap();
+
+    let mut failures = 0;
+    for fixture in &fixtures {
+        for &backend in BACKEND_NAMES.iter() {
+            let (verdict, elapsed) = run_fixture(&exe, backend, fixture);
+            let millis = elapsed.as_secs_f64() * 1000.0;
+            let status = match verdict {
+                Verdict::Correct => "PASS",
+                _ => "FAIL",
+            };
+            println!("{} {:<32} {:<20} {:>10.2} ms", status, fixture.name, backend, millis);
+
+            match verdict {
+                Verdict::Correct => continue,
+                Verdict::Incorrect(diffs) => for diff in diffs { println!("    {}", diff) },
+                Verdict::Error(e) => println!("    {}", e),
+            }
+            failures += 1;
+        }
+    }
+
+    let total = fixtures.len() * BACKEND_NAMES.len();
+    println!("{} of {} runs passed", total - failures, total);
+    failures == 0
+}
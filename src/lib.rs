@@ -0,0 +1,29 @@
+//! Solutions to the `IbanCalculator` assignment, using different backends.
+//!
+//! The binary in `main.rs` reads the assignment input from stdin and runs it
+//! with one of the backends. The library is exposed separately so the backends
+//! can be tested and compared against each other.
+
+extern crate rayon;
+extern crate sha1;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(loom)]
+extern crate loom;
+
+pub mod backend;
+pub mod bench;
+pub mod csv;
+pub mod digest;
+pub mod hash;
+pub mod iban;
+pub mod judge;
+pub mod lock;
+pub mod output;
+pub mod parse;
+pub mod settings;
+mod sync;
+pub mod util;
+pub mod validate;
@@ -1,9 +1,19 @@
-use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
 use std::ops::{Deref, DerefMut};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sync::{AtomicBool, Ordering, UnsafeCell};
+
+use super::spin_strategy::{SpinStrategy, SpinWait};
 
 pub struct AdvancedSpinLock<T> {
     lock: AtomicBool,
+    /// True if a thread panicked while holding the lock
+    poisoned: AtomicBool,
+    strategy: SpinStrategy,
     data: UnsafeCell<T>,
 }
 unsafe impl<T: Send> Send for AdvancedSpinLock<T> { }
@@ -12,16 +22,35 @@ unsafe impl<T: Send> Sync for AdvancedSpinLock<T> { }
 impl<T> AdvancedSpinLock<T> {
     /// Creates a new AdvancedSpinLock in an unlocked state ready for use.
     pub fn new(t: T) -> AdvancedSpinLock<T> {
+        AdvancedSpinLock::with_strategy(t, SpinStrategy::default())
+    }
+
+    /// Creates a new AdvancedSpinLock that waits according to the given strategy.
+    pub fn with_strategy(t: T, strategy: SpinStrategy) -> AdvancedSpinLock<T> {
         AdvancedSpinLock {
             lock: AtomicBool::new(false),
+            poisoned: AtomicBool::new(false),
+            strategy,
             data: UnsafeCell::new(t),
         }
     }
 
     /// Acquires a SpinLockGuard, spinning the current thread until it is able to do so.
-    pub fn lock(&self) -> SpinLockGuard<T> {
-        while self.lock.compare_and_swap(false, true, Ordering::SeqCst) {
-            while self.lock.load(Ordering::SeqCst) { }
+    ///
+    /// Like `std::sync::Mutex::lock`, this returns an error containing the guard
+    /// if another thread panicked while holding the lock.
+    pub fn lock(&self) -> LockResult<SpinLockGuard<'_, T>> {
+        self.lock_with(|| {})
+    }
+
+    /// `lock`, calling `failed` after every failed CaS, see `Lock::lock_counting`
+    pub(super) fn lock_with<F: FnMut()>(&self, mut failed: F) -> LockResult<SpinLockGuard<'_, T>> {
+        // Only the successful CaS needs to synchronize with the release in
+        // `SpinLockGuard::drop`; waiting for the lock to look free can be `Relaxed`
+        let mut spin_wait = SpinWait::new(self.strategy);
+        while !self.try_take_lock() {
+            failed();
+            while self.lock.load(Ordering::Relaxed) { spin_wait.wait() }
         }
 
         // Exit the spinning wait, holding the lock
@@ -29,24 +58,86 @@ impl<T> AdvancedSpinLock<T> {
 
         // Note: the lock will be released by `SpinLockGuard` when it falls out of scope
     }
+
+    /// Attempts to acquire a SpinLockGuard without spinning.
+    pub fn try_lock(&self) -> TryLockResult<SpinLockGuard<'_, T>> {
+        if self.try_take_lock() {
+            Ok(unsafe { SpinLockGuard::new(self) }?)
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Attempts to acquire a SpinLockGuard, spinning for at most `timeout`.
+    pub fn try_lock_for(&self, timeout: Duration) -> TryLockResult<SpinLockGuard<'_, T>> {
+        let deadline = Instant::now() + timeout;
+        let mut spin_wait = SpinWait::new(self.strategy);
+        while !self.try_take_lock() {
+            if Instant::now() >= deadline {
+                return Err(TryLockError::WouldBlock);
+            }
+            spin_wait.wait();
+        }
+
+        Ok(unsafe { SpinLockGuard::new(self) }?)
+    }
+
+    /// Returns true if a thread panicked while holding the lock.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Consumes the lock, returning the data.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let data = self.data.into_inner();
+        if poisoned { Err(PoisonError::new(data)) } else { Ok(data) }
+    }
+
+    /// Returns a mutable reference to the data, without locking: the mutable
+    /// borrow guarantees that nobody else holds the lock.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let data = self.data.with_mut(|data| unsafe { &mut *data });
+        if poisoned { Err(PoisonError::new(data)) } else { Ok(data) }
+    }
+
+    fn try_take_lock(&self) -> bool {
+        self.lock.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
 }
 
 pub struct SpinLockGuard<'a, T: 'a> {
     __spin_lock: &'a AdvancedSpinLock<T>,
+    /// True if the thread was already panicking when it took the lock, in
+    /// which case dropping the guard while unwinding doesn't poison the lock
+    __panicking: bool,
+    /// Opts out of `Send` and `Sync`, like `MutexGuard`: a shared guard hands
+    /// out `&T`, so it may only be `Sync` if `T: Sync`, not just `T: Send`
+    __marker: PhantomData<*const ()>,
 }
 
+unsafe impl<'a, T: Sync> Sync for SpinLockGuard<'a, T> { }
+
 impl<'a, T> SpinLockGuard<'a, T> {
-    unsafe fn new(lock: &'a AdvancedSpinLock<T>) -> SpinLockGuard<'a, T> {
-        SpinLockGuard {
+    unsafe fn new(lock: &'a AdvancedSpinLock<T>) -> LockResult<SpinLockGuard<'a, T>> {
+        let guard = SpinLockGuard {
             __spin_lock: lock,
-        }
+            __panicking: thread::panicking(),
+            __marker: PhantomData,
+        };
+
+        if lock.is_poisoned() { Err(PoisonError::new(guard)) } else { Ok(guard) }
     }
 }
 
 impl<'a, T> Drop for SpinLockGuard<'a, T> {
     #[inline]
     fn drop(&mut self) {
-        self.__spin_lock.lock.store(false, Ordering::SeqCst);
+        if !self.__panicking && thread::panicking() {
+            self.__spin_lock.poisoned.store(true, Ordering::Relaxed);
+        }
+        self.__spin_lock.lock.store(false, Ordering::Release);
     }
 }
 
@@ -56,12 +147,18 @@ impl<'a, T> Deref for SpinLockGuard<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { &*self.__spin_lock.data.get() }
+        self.__spin_lock.data.with(|data| unsafe { &*data })
     }
 }
 
 impl<'a, T> DerefMut for SpinLockGuard<'a, T> {
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.__spin_lock.data.get() }
+        self.__spin_lock.data.with_mut(|data| unsafe { &mut *data })
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for SpinLockGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
     }
 }
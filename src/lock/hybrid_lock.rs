@@ -0,0 +1,102 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::hint;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The amount of times a thread tries to take the lock before going to sleep
+const SPIN_LIMIT: usize = 100;
+
+const UNLOCKED: u8 = 0;
+const LOCKED: u8 = 1;
+/// Locked, and there may be sleeping threads that need to be woken up
+const CONTENDED: u8 = 2;
+
+/// A lock that spins for a short while and then parks the thread
+///
+/// Short critical sections are handled as cheaply as by a spin lock, while
+/// threads that would otherwise spin for a long time (e.g. when there are more
+/// threads than cores) give their core to the thread that holds the lock.
+pub struct HybridLock<T> {
+    state: AtomicU8,
+    /// Used to park threads, protects nothing by itself
+    sleepers: Mutex<()>,
+    wake_up: Condvar,
+    data: UnsafeCell<T>,
+}
+unsafe impl<T: Send> Send for HybridLock<T> { }
+unsafe impl<T: Send> Sync for HybridLock<T> { }
+
+impl<T> HybridLock<T> {
+    /// Creates a new HybridLock in an unlocked state ready for use.
+    pub fn new(t: T) -> HybridLock<T> {
+        HybridLock {
+            state: AtomicU8::new(UNLOCKED),
+            sleepers: Mutex::new(()),
+            wake_up: Condvar::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Acquires a HybridLockGuard, spinning and then sleeping until it is able to do so.
+    pub fn lock(&self) -> HybridLockGuard<'_, T> {
+        self.lock_with(|| {})
+    }
+
+    /// `lock`, calling `failed` after every failed CaS, see `Lock::lock_counting`
+    pub(super) fn lock_with<F: FnMut()>(&self, mut failed: F) -> HybridLockGuard<'_, T> {
+        for _ in 0..SPIN_LIMIT {
+            if self.state.compare_exchange_weak(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                return HybridLockGuard { lock: self, _marker: PhantomData };
+            }
+            failed();
+            hint::spin_loop();
+        }
+
+        // We can't tell whether there are other sleepers, so the lock is marked
+        // as contended even if we take it here
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            // The state is checked with `sleepers` locked, and the holder of the
+            // lock takes `sleepers` before notifying, so no wake up is lost
+            let mut sleepers = self.sleepers.lock().unwrap();
+            while self.state.load(Ordering::Relaxed) == CONTENDED {
+                sleepers = self.wake_up.wait(sleepers).unwrap();
+            }
+        }
+
+        HybridLockGuard { lock: self, _marker: PhantomData }
+    }
+}
+
+pub struct HybridLockGuard<'a, T: 'a> {
+    lock: &'a HybridLock<T>,
+    /// Opts out of `Send` and `Sync`, like `MutexGuard`: a shared guard hands
+    /// out `&T`, so it may only be `Sync` if `T: Sync`, not just `T: Send`
+    _marker: PhantomData<*const ()>,
+}
+
+unsafe impl<'a, T: Sync> Sync for HybridLockGuard<'a, T> { }
+
+impl<'a, T> Drop for HybridLockGuard<'a, T> {
+    fn drop(&mut self) {
+        if self.lock.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            let _sleepers = self.lock.sleepers.lock().unwrap();
+            self.lock.wake_up.notify_one();
+        }
+    }
+}
+
+impl<'a, T> Deref for HybridLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for HybridLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
@@ -0,0 +1,145 @@
+use std::cell::{RefCell, UnsafeCell};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use super::spin_strategy::{SpinStrategy, SpinWait};
+
+/// A queue node, one for each thread that holds or waits for the lock
+struct Node {
+    /// True while the thread owning the node has to wait
+    locked: AtomicBool,
+    /// The thread that queued up after the owner of this node
+    next: AtomicPtr<Node>,
+}
+
+thread_local! {
+    /// The nodes of this thread that no other thread points to anymore
+    ///
+    /// Reusing them keeps the allocator out of `lock`. It is a stack, because a
+    /// thread that holds several locks at once needs a node for each of them.
+    /// The nodes are boxed, so they keep their address when they are taken.
+    #[allow(clippy::vec_box)]
+    static FREE_NODES: RefCell<Vec<Box<Node>>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Node {
+    /// Takes a free node of this thread, or allocates one if there is none
+    ///
+    /// The node is on the heap, so its address stays the same while other
+    /// threads point to it.
+    fn take() -> *mut Node {
+        let node = FREE_NODES.with(|nodes| nodes.borrow_mut().pop()).unwrap_or_else(|| Box::new(Node {
+            locked: AtomicBool::new(true),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        // Published by the swap of the tail in `McsLock::lock`
+        node.locked.store(true, Ordering::Relaxed);
+        node.next.store(ptr::null_mut(), Ordering::Relaxed);
+        Box::into_raw(node)
+    }
+
+    /// Gives a node of `take` back to this thread, once nobody else points to it
+    unsafe fn give_back(node: *mut Node) {
+        let node = Box::from_raw(node);
+        // While the thread exits, the free nodes may be gone already, and the
+        // node is freed instead
+        let _ = FREE_NODES.try_with(|nodes| nodes.borrow_mut().push(node));
+    }
+}
+
+/// A fair spin lock, as described by Mellor-Crummey and Scott
+///
+/// Waiting threads form a queue. Unlike with `AdvancedSpinLock` or `TicketLock`,
+/// each thread spins on a flag in its own node, so a release only touches the
+/// cache line of the next thread in the queue.
+pub struct McsLock<T> {
+    /// The last node in the queue, or null if the lock is free
+    tail: AtomicPtr<Node>,
+    data: UnsafeCell<T>,
+}
+unsafe impl<T: Send> Send for McsLock<T> { }
+unsafe impl<T: Send> Sync for McsLock<T> { }
+
+impl<T> McsLock<T> {
+    /// Creates a new McsLock in an unlocked state ready for use.
+    pub fn new(t: T) -> McsLock<T> {
+        McsLock {
+            tail: AtomicPtr::new(ptr::null_mut()),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Acquires an McsLockGuard, spinning the current thread until it is able to do so.
+    pub fn lock(&self) -> McsLockGuard<'_, T> {
+        let node = Node::take();
+
+        let prev = self.tail.swap(node, Ordering::AcqRel);
+        if !prev.is_null() {
+            // Someone holds the lock: queue up behind them and wait until they
+            // hand the lock over. The previous node stays alive until its owner
+            // has seen our node in `next`, see `McsLockGuard::drop`.
+            unsafe {
+                (*prev).next.store(node, Ordering::Release);
+                let mut spin_wait = SpinWait::new(SpinStrategy::SpinThenYield);
+                while (*node).locked.load(Ordering::Acquire) {
+                    spin_wait.wait();
+                }
+            }
+        }
+
+        McsLockGuard { lock: self, node }
+    }
+}
+
+/// The guard of an `McsLock`
+///
+/// It isn't `Send`, because of the raw pointer, so it is dropped by the thread
+/// that took the lock and its node goes back to the free nodes of that thread.
+pub struct McsLockGuard<'a, T: 'a> {
+    lock: &'a McsLock<T>,
+    node: *mut Node,
+}
+
+impl<'a, T> Drop for McsLockGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            let mut next = (*self.node).next.load(Ordering::Acquire);
+            if next.is_null() {
+                // Nobody seems to be waiting, so try to mark the lock as free
+                let free = self.lock.tail.compare_exchange(
+                    self.node, ptr::null_mut(), Ordering::Release, Ordering::Relaxed);
+                if free.is_ok() {
+                    Node::give_back(self.node);
+                    return;
+                }
+
+                // A thread swapped the tail but didn't link itself yet
+                let mut spin_wait = SpinWait::new(SpinStrategy::SpinThenYield);
+                loop {
+                    next = (*self.node).next.load(Ordering::Acquire);
+                    if !next.is_null() { break }
+                    spin_wait.wait();
+                }
+            }
+
+            // Hand the lock over. Nobody else points to our node anymore.
+            (*next).locked.store(false, Ordering::Release);
+            Node::give_back(self.node);
+        }
+    }
+}
+
+impl<'a, T> Deref for McsLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for McsLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
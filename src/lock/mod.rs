@@ -9,9 +9,155 @@
 //! so the lock can be released when the guard is dropped. This requires
 //! getting the lifetimes right in order to avoid dangling pointers. There is
 //! also slightly more unsafe code than in `SpinLock`.
+//!
+//! Both spinlocks are unfair: a thread that just released the lock is likely to
+//! take it again, while others keep waiting. The module also contains fair and
+//! more scalable alternatives, with the same guard-based API as `AdvancedSpinLock`:
+//!
+//! * `TicketLock` serves threads in the order in which they arrived.
+//! * `McsLock` is a queue lock, where each thread spins on its own flag instead
+//!   of on a shared one, so releasing the lock doesn't thrash the cache.
+//! * `HybridLock` spins for a short while and then puts the thread to sleep.
+//!
+//! For data that is read far more often than it is written, `RwSpinLock` lets
+//! any amount of readers in at the same time, or a single writer. It uses the
+//! same guard-based API, with separate read, write and upgradable read guards.
+//!
+//! The spin locks wait according to a `SpinStrategy`, which defaults to spinning
+//! for a while and then yielding, so that oversubscribed runs (more threads than
+//! cores) don't stall the thread holding the lock. The fair locks always use that
+//! default: with a fair lock, only the next thread in line can make progress.
+//!
+//! `SpinLock` and `AdvancedSpinLock` support `try_lock`, `try_lock_for` and
+//! poisoning with the same semantics as `std::sync::Mutex`: when a thread panics
+//! while holding the lock, the lock is released and later attempts to take it
+//! return a `PoisonError`. This way a panicking worker makes the others fail as
+//! well, instead of leaving them spinning forever. `RwSpinLock` is poisoned in
+//! the same way when a writer panics.
+//!
+//! Any lock can be wrapped in `Instrumented`, which records how often it was
+//! taken, how long threads waited for it and how long they held it. The spin
+//! locks also report how many CaS attempts failed; they only count them when
+//! they are instrumented, see `Lock::lock_counting`.
+//!
+//! Code that needs to be generic over the lock it uses, such as
+//! `backend::GuardedLockBackend`, can do so through the `Lock` and `LockKind`
+//! traits.
 
 mod advanced_spin_lock;
+mod hybrid_lock;
+mod mcs_lock;
+mod rw_spin_lock;
 mod spin_lock;
+mod spin_strategy;
+pub mod stats;
+mod ticket_lock;
+
+use std::ops::DerefMut;
+use std::sync::{LockResult, Mutex, MutexGuard};
 
 pub use self::advanced_spin_lock::AdvancedSpinLock;
+pub use self::hybrid_lock::HybridLock;
+pub use self::mcs_lock::McsLock;
+pub use self::rw_spin_lock::{RwSpinLock, RwSpinLockReadGuard, RwSpinLockUpgradableGuard, RwSpinLockWriteGuard};
 pub use self::spin_lock::SpinLock;
+pub use self::spin_strategy::SpinStrategy;
+pub use self::stats::{Instrumented, LockSummary};
+pub use self::ticket_lock::TicketLock;
+
+/// A lock that hands out a guard, which releases the lock when dropped
+///
+/// Like `std::sync::Mutex`, `lock` returns an error if the lock is poisoned,
+/// because a thread panicked while holding it. Locks without poisoning support
+/// always return `Ok`.
+pub trait Lock<T>: Send + Sync {
+    type Guard<'a>: DerefMut<Target=T> where Self: 'a;
+
+    fn new(data: T) -> Self;
+    fn lock(&self) -> LockResult<Self::Guard<'_>>;
+
+    /// Like `lock`, but adds the failed CaS attempts while waiting to
+    /// `failed_attempts`, for `Instrumented`
+    ///
+    /// Only the locks that spin on a CaS count them, the others keep this
+    /// default. `lock` never counts, so a lock that isn't instrumented spins
+    /// without any bookkeeping.
+    fn lock_counting(&self, _failed_attempts: &mut u64) -> LockResult<Self::Guard<'_>> {
+        self.lock()
+    }
+}
+
+/// Selects a `Lock` for any type of data
+///
+/// The types in the `kind` module implement this trait.
+pub trait LockKind {
+    type Lock<T: Send>: Lock<T>;
+}
+
+/// Marker types to select a lock through `LockKind`
+pub mod kind {
+    pub enum StdMutex { }
+    pub enum AdvancedSpinLock { }
+    pub enum TicketLock { }
+    pub enum McsLock { }
+    pub enum HybridLock { }
+}
+
+macro_rules! impl_lock {
+    ($kind:ident, $lock:ident, $guard:ty) => {
+        impl<T: Send> Lock<T> for $lock<T> {
+            type Guard<'a> = $guard where T: 'a;
+
+            fn new(data: T) -> Self { $lock::new(data) }
+            fn lock(&self) -> LockResult<Self::Guard<'_>> { Ok($lock::lock(self)) }
+        }
+
+        impl LockKind for kind::$kind {
+            type Lock<T: Send> = $lock<T>;
+        }
+    }
+}
+
+impl_lock!(TicketLock, TicketLock, ticket_lock::TicketLockGuard<'a, T>);
+impl_lock!(McsLock, McsLock, mcs_lock::McsLockGuard<'a, T>);
+
+impl<T: Send> Lock<T> for HybridLock<T> {
+    type Guard<'a> = hybrid_lock::HybridLockGuard<'a, T> where T: 'a;
+
+    fn new(data: T) -> Self { HybridLock::new(data) }
+    fn lock(&self) -> LockResult<Self::Guard<'_>> { Ok(HybridLock::lock(self)) }
+
+    fn lock_counting(&self, failed_attempts: &mut u64) -> LockResult<Self::Guard<'_>> {
+        Ok(self.lock_with(|| *failed_attempts += 1))
+    }
+}
+
+impl LockKind for kind::HybridLock {
+    type Lock<T: Send> = HybridLock<T>;
+}
+
+impl<T: Send> Lock<T> for AdvancedSpinLock<T> {
+    type Guard<'a> = advanced_spin_lock::SpinLockGuard<'a, T> where T: 'a;
+
+    fn new(data: T) -> Self { AdvancedSpinLock::new(data) }
+    fn lock(&self) -> LockResult<Self::Guard<'_>> { AdvancedSpinLock::lock(self) }
+
+    fn lock_counting(&self, failed_attempts: &mut u64) -> LockResult<Self::Guard<'_>> {
+        self.lock_with(|| *failed_attempts += 1)
+    }
+}
+
+impl LockKind for kind::AdvancedSpinLock {
+    type Lock<T: Send> = AdvancedSpinLock<T>;
+}
+
+impl<T: Send> Lock<T> for Mutex<T> {
+    type Guard<'a> = MutexGuard<'a, T> where T: 'a;
+
+    fn new(data: T) -> Self { Mutex::new(data) }
+    fn lock(&self) -> LockResult<Self::Guard<'_>> { Mutex::lock(self) }
+}
+
+impl LockKind for kind::StdMutex {
+    type Lock<T: Send> = Mutex<T>;
+}
@@ -0,0 +1,300 @@
+use std::cell::UnsafeCell;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use super::spin_strategy::{SpinStrategy, SpinWait};
+
+/// Set while a writer holds the lock
+const WRITER: usize = 1;
+/// Set by writers (and upgrading readers) that are waiting for the lock, so no
+/// new readers come in and the writer isn't starved
+const WRITER_WAITING: usize = 2;
+/// Set while a thread holds an upgradable read guard
+const UPGRADABLE: usize = 4;
+/// The state holds this value times the amount of readers
+const READER: usize = 8;
+
+/// A reader-writer spin lock: any amount of readers, or a single writer
+///
+/// Writers are preferred: once a writer is waiting, new readers wait until it
+/// is done, so a steady stream of readers can't starve it. One of the readers
+/// can hold an upgradable guard, which can later be turned into a write guard
+/// without letting another writer in between.
+pub struct RwSpinLock<T> {
+    /// The bits above, plus `READER` times the amount of readers
+    state: AtomicUsize,
+    /// True if a thread panicked while holding a write guard
+    poisoned: AtomicBool,
+    strategy: SpinStrategy,
+    data: UnsafeCell<T>,
+}
+unsafe impl<T: Send> Send for RwSpinLock<T> { }
+unsafe impl<T: Send + Sync> Sync for RwSpinLock<T> { }
+
+impl<T> RwSpinLock<T> {
+    /// Creates a new RwSpinLock in an unlocked state ready for use.
+    pub fn new(t: T) -> RwSpinLock<T> {
+        RwSpinLock::with_strategy(t, SpinStrategy::default())
+    }
+
+    /// Creates a new RwSpinLock that waits according to the given strategy.
+    pub fn with_strategy(t: T, strategy: SpinStrategy) -> RwSpinLock<T> {
+        RwSpinLock {
+            state: AtomicUsize::new(0),
+            poisoned: AtomicBool::new(false),
+            strategy,
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Acquires a read guard, spinning while a writer holds or waits for the lock.
+    pub fn read(&self) -> LockResult<RwSpinLockReadGuard<'_, T>> {
+        let mut spin_wait = SpinWait::new(self.strategy);
+        while !self.try_take_read() { spin_wait.wait() }
+        self.poison_result(RwSpinLockReadGuard { lock: self })
+    }
+
+    /// Attempts to acquire a read guard without spinning.
+    pub fn try_read(&self) -> TryLockResult<RwSpinLockReadGuard<'_, T>> {
+        if !self.try_take_read() {
+            return Err(TryLockError::WouldBlock);
+        }
+        Ok(self.poison_result(RwSpinLockReadGuard { lock: self })?)
+    }
+
+    /// Acquires an upgradable read guard, spinning while a writer or another
+    /// upgradable reader holds the lock, or a writer waits for it.
+    pub fn upgradable_read(&self) -> LockResult<RwSpinLockUpgradableGuard<'_, T>> {
+        let mut spin_wait = SpinWait::new(self.strategy);
+        while !self.try_take_upgradable() { spin_wait.wait() }
+        self.poison_result(RwSpinLockUpgradableGuard { lock: self })
+    }
+
+    /// Attempts to acquire an upgradable read guard without spinning.
+    pub fn try_upgradable_read(&self) -> TryLockResult<RwSpinLockUpgradableGuard<'_, T>> {
+        if !self.try_take_upgradable() {
+            return Err(TryLockError::WouldBlock);
+        }
+        Ok(self.poison_result(RwSpinLockUpgradableGuard { lock: self })?)
+    }
+
+    /// Acquires a write guard, spinning until all other guards are dropped.
+    pub fn write(&self) -> LockResult<RwSpinLockWriteGuard<'_, T>> {
+        let mut spin_wait = SpinWait::new(self.strategy);
+        while !self.try_take_write() {
+            // Keep new readers out. The flag is cleared by the writer that
+            // takes the lock next, so waiting writers keep setting it again.
+            self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            spin_wait.wait();
+        }
+        self.poison_result(RwSpinLockWriteGuard::new(self))
+    }
+
+    /// Attempts to acquire a write guard without spinning.
+    pub fn try_write(&self) -> TryLockResult<RwSpinLockWriteGuard<'_, T>> {
+        if !self.try_take_write() {
+            return Err(TryLockError::WouldBlock);
+        }
+        Ok(self.poison_result(RwSpinLockWriteGuard::new(self))?)
+    }
+
+    /// Returns true if a thread panicked while holding a write guard.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Consumes the lock, returning the data.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let data = self.data.into_inner();
+        if poisoned { Err(PoisonError::new(data)) } else { Ok(data) }
+    }
+
+    /// Returns a mutable reference to the data, without locking: the mutable
+    /// borrow guarantees that nobody else holds the lock.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let data = self.data.get_mut();
+        if poisoned { Err(PoisonError::new(data)) } else { Ok(data) }
+    }
+
+    fn poison_result<G>(&self, guard: G) -> LockResult<G> {
+        if self.is_poisoned() { Err(PoisonError::new(guard)) } else { Ok(guard) }
+    }
+
+    // Like in `AdvancedSpinLock`, taking the lock needs `Acquire` to synchronize
+    // with the `Release` in the drop of the previous guard, and failed attempts
+    // can be `Relaxed`
+
+    fn try_take_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & (WRITER | WRITER_WAITING) != 0 {
+                return false;
+            }
+            match self.state.compare_exchange_weak(state, state + READER, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return true,
+                // Another reader came or went, try again
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    fn try_take_upgradable(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & (WRITER | WRITER_WAITING | UPGRADABLE) != 0 {
+                return false;
+            }
+            match self.state.compare_exchange_weak(state, state | UPGRADABLE, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    fn try_take_write(&self) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+        state & !WRITER_WAITING == 0
+            && self.state.compare_exchange(state, WRITER, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    /// Turn the upgradable read guard of the caller into a write guard, once
+    /// all other readers are gone
+    fn try_take_upgrade(&self) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+        state & !WRITER_WAITING == UPGRADABLE
+            && self.state.compare_exchange(state, WRITER, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+}
+
+/// Shared access to the data of a `RwSpinLock`
+pub struct RwSpinLockReadGuard<'a, T: 'a> {
+    lock: &'a RwSpinLock<T>,
+}
+
+impl<'a, T> Drop for RwSpinLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        // `Release`, so our reads happen before the next writer's writes
+        self.lock.state.fetch_sub(READER, Ordering::Release);
+    }
+}
+
+impl<'a, T> Deref for RwSpinLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+/// Shared access to the data of a `RwSpinLock`, that can be upgraded to
+/// exclusive access
+pub struct RwSpinLockUpgradableGuard<'a, T: 'a> {
+    lock: &'a RwSpinLock<T>,
+}
+
+impl<'a, T> RwSpinLockUpgradableGuard<'a, T> {
+    /// Waits until all other readers are gone, and returns a write guard.
+    pub fn upgrade(self) -> RwSpinLockWriteGuard<'a, T> {
+        let lock = self.lock;
+        let mut spin_wait = SpinWait::new(lock.strategy);
+        while !lock.try_take_upgrade() {
+            // Like a waiting writer, keep new readers out
+            lock.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            spin_wait.wait();
+        }
+
+        // The upgradable bit was cleared together with taking the write lock
+        mem::forget(self);
+        RwSpinLockWriteGuard::new(lock)
+    }
+
+    /// Returns a write guard if there are no other readers, or the upgradable
+    /// guard otherwise.
+    pub fn try_upgrade(self) -> Result<RwSpinLockWriteGuard<'a, T>, RwSpinLockUpgradableGuard<'a, T>> {
+        if !self.lock.try_take_upgrade() {
+            return Err(self);
+        }
+
+        let lock = self.lock;
+        mem::forget(self);
+        Ok(RwSpinLockWriteGuard::new(lock))
+    }
+
+    /// Turns the guard into a plain read guard, so another thread can take an
+    /// upgradable guard.
+    pub fn downgrade(self) -> RwSpinLockReadGuard<'a, T> {
+        let lock = self.lock;
+        mem::forget(self);
+
+        // Clear the upgradable bit and add a reader in a single step
+        lock.state.fetch_add(READER - UPGRADABLE, Ordering::Release);
+        RwSpinLockReadGuard { lock }
+    }
+}
+
+impl<'a, T> Drop for RwSpinLockUpgradableGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_and(!UPGRADABLE, Ordering::Release);
+    }
+}
+
+impl<'a, T> Deref for RwSpinLockUpgradableGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+/// Exclusive access to the data of a `RwSpinLock`
+pub struct RwSpinLockWriteGuard<'a, T: 'a> {
+    lock: &'a RwSpinLock<T>,
+    /// True if the thread was already panicking when it took the lock, in
+    /// which case dropping the guard while unwinding doesn't poison the lock
+    panicking: bool,
+}
+
+impl<'a, T> RwSpinLockWriteGuard<'a, T> {
+    fn new(lock: &'a RwSpinLock<T>) -> RwSpinLockWriteGuard<'a, T> {
+        RwSpinLockWriteGuard { lock, panicking: thread::panicking() }
+    }
+
+    /// Turns the guard into a read guard, without letting a writer in between.
+    pub fn downgrade(self) -> RwSpinLockReadGuard<'a, T> {
+        let lock = self.lock;
+        mem::forget(self);
+
+        // Clear the writer bit and add a reader in a single step, while keeping
+        // the waiting flag of other writers
+        lock.state.fetch_add(READER - WRITER, Ordering::Release);
+        RwSpinLockReadGuard { lock }
+    }
+}
+
+impl<'a, T> Drop for RwSpinLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        if !self.panicking && thread::panicking() {
+            self.lock.poisoned.store(true, Ordering::Relaxed);
+        }
+        self.lock.state.fetch_and(!WRITER, Ordering::Release);
+    }
+}
+
+impl<'a, T> Deref for RwSpinLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for RwSpinLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
@@ -1,5 +1,10 @@
-use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sync::{AtomicBool, Ordering, UnsafeCell};
+
+use super::spin_strategy::{SpinStrategy, SpinWait};
 
 pub struct SpinLock<T> {
     /// The data protected by this lock
@@ -9,21 +14,95 @@ pub struct SpinLock<T> {
     data: UnsafeCell<T>,
     /// True if the lock is taken
     lock_taken: AtomicBool,
+    /// True if a closure panicked while holding the lock
+    poisoned: AtomicBool,
+    /// How to wait while the lock is taken
+    strategy: SpinStrategy,
 }
 
 impl<T> SpinLock<T> {
-    /// Return a released `SpinLock`
+    /// Return a released `SpinLock`, using the default `SpinStrategy`
     pub fn new(data: T) -> SpinLock<T> {
+        SpinLock::with_strategy(data, SpinStrategy::default())
+    }
+
+    /// Return a released `SpinLock`, that waits according to the given strategy
+    pub fn with_strategy(data: T, strategy: SpinStrategy) -> SpinLock<T> {
         SpinLock {
             data: UnsafeCell::new(data),
-            lock_taken: AtomicBool::new(false)
+            lock_taken: AtomicBool::new(false),
+            poisoned: AtomicBool::new(false),
+            strategy,
+        }
+    }
+
+    /// Execute the given closure after acquiring the lock, release the lock
+    /// afterwards and return the result of the closure.
+    ///
+    /// If the closure panics, the lock is still released, but it is poisoned:
+    /// like `std::sync::Mutex`, later calls still run their closure, but return
+    /// its result in an error to signal that the data may be in an inconsistent
+    /// state.
+    pub fn lock<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> LockResult<R> {
+        self.take_lock(|| {});
+        self.locked(f)
+    }
+
+    /// Like `lock`, but also passes the amount of failed CaS attempts while
+    /// waiting to the closure, for `Instrumented`
+    pub(super) fn lock_counting<R, F: FnOnce(&mut T, u64) -> R>(&self, f: F) -> LockResult<R> {
+        let mut failed_attempts = 0;
+        self.take_lock(|| failed_attempts += 1);
+        self.locked(|data| f(data, failed_attempts))
+    }
+
+    /// Execute the given closure if the lock can be acquired without spinning.
+    pub fn try_lock<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> TryLockResult<R> {
+        if !self.try_take_lock() {
+            return Err(TryLockError::WouldBlock);
         }
+        Ok(self.locked(f)?)
     }
 
-    /// Execute the given closure after acquiring the lock, and release the
-    /// lock afterwards.
-    pub fn lock<F: Fn(&mut T)>(&self, f: F) {
-        self.take_lock();
+    /// Execute the given closure if the lock can be acquired within `timeout`.
+    pub fn try_lock_for<R, F: FnOnce(&mut T) -> R>(&self, timeout: Duration, f: F) -> TryLockResult<R> {
+        let deadline = Instant::now() + timeout;
+        let mut spin_wait = SpinWait::new(self.strategy);
+        while !self.try_take_lock() {
+            if Instant::now() >= deadline {
+                return Err(TryLockError::WouldBlock);
+            }
+            spin_wait.wait();
+        }
+        Ok(self.locked(f)?)
+    }
+
+    /// Returns true if a closure panicked while holding the lock.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Consumes the lock, returning the data.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let data = self.data.into_inner();
+        if poisoned { Err(PoisonError::new(data)) } else { Ok(data) }
+    }
+
+    /// Returns a mutable reference to the data, without locking: the mutable
+    /// borrow guarantees that nobody else holds the lock.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let data = self.data.with_mut(|data| unsafe { &mut *data });
+        if poisoned { Err(PoisonError::new(data)) } else { Ok(data) }
+    }
+
+    /// Run the closure on the data, while holding the lock
+    fn locked<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> LockResult<R> {
+        let poisoned = self.is_poisoned();
+
+        // Releases the lock when it falls out of scope, also when `f` panics
+        let _release = ReleaseOnDrop { lock: self, panicking: thread::panicking() };
 
         // Obtaining a `&mut T` from `UnsafeCell<T>` is unsafe because the compiler
         // cannot verify that the obtained reference is unique. In other words,
@@ -32,26 +111,58 @@ impl<T> SpinLock<T> {
         // result in undefined behavior.
         //
         // In this case, we know that this is the only reference to the data, because
-        // you need to acquire the lock before getting here.
-        let data = unsafe { &mut *self.data.get() };
-        f(data);
+        // you need to acquire the lock before getting here. The reference can't
+        // escape through the result either, since `R` can't borrow from the
+        // higher-ranked lifetime of the closure argument.
+        let result = self.data.with_mut(|data| f(unsafe { &mut *data }));
 
-        self.release_lock();
+        if poisoned { Err(PoisonError::new(result)) } else { Ok(result) }
     }
 
-    fn take_lock(&self) {
-        // Note that this function could be optimized further (TTaS instead of TaS)
+    /// Takes the lock, calling `failed` after every failed CaS
+    fn take_lock<F: FnMut()>(&self, mut failed: F) {
+        // Try to take the lock with a CaS, which only swaps the values if the
+        // previous one was false. After every failed attempt, wait as the
+        // `SpinStrategy` of the lock says (spinning, pausing, backing off or
+        // yielding) before trying again. `AdvancedSpinLock` reads the flag
+        // while waiting instead (TTaS); this lock always retries the CaS.
+        //
+        // Taking the lock needs `Acquire`, so we see everything the previous
+        // holder wrote before releasing it. A failed attempt doesn't synchronize
+        // with anything, so it can be `Relaxed`.
+        let mut spin_wait = SpinWait::new(self.strategy);
+        while self.lock_taken.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            failed();
+            spin_wait.wait();
+        }
+    }
 
-        // Spin while the lock is taken
-        // In each iteration, try to take the lock
-        // CaS will only swap the values if the previous one was false
-        while self.lock_taken.compare_and_swap(false, true, Ordering::SeqCst) {}
+    fn try_take_lock(&self) -> bool {
+        self.lock_taken.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
     }
 
     fn release_lock(&self) {
-        self.lock_taken.store(false, Ordering::SeqCst);
+        // `Release` makes our writes to the data visible to the next holder
+        self.lock_taken.store(false, Ordering::Release);
     }
 }
 
 unsafe impl<T: Send> Sync for SpinLock<T> {}
 unsafe impl<T: Send> Send for SpinLock<T> {}
+
+/// Releases a `SpinLock` when dropped, poisoning it if the thread started
+/// panicking while holding the lock
+struct ReleaseOnDrop<'a, T: 'a> {
+    lock: &'a SpinLock<T>,
+    /// True if the thread was already panicking when it took the lock
+    panicking: bool,
+}
+
+impl<'a, T> Drop for ReleaseOnDrop<'a, T> {
+    fn drop(&mut self) {
+        if !self.panicking && thread::panicking() {
+            self.lock.poisoned.store(true, Ordering::Relaxed);
+        }
+        self.lock.release_lock();
+    }
+}
@@ -0,0 +1,64 @@
+use sync::{spin_loop, thread};
+
+/// The amount of times `SpinThenYield` spins before it starts yielding
+const SPIN_LIMIT: u32 = 100;
+
+/// The maximum amount of pauses between two attempts with `ExponentialBackoff`,
+/// as a power of two
+const MAX_BACKOFF_STEP: u32 = 10;
+
+/// How a thread waits while the lock it wants is taken
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpinStrategy {
+    /// Busy loop, without telling the CPU that we are waiting
+    Spin,
+    /// Busy loop with a pause instruction in every iteration (`std::hint::spin_loop`),
+    /// which saves power and frees up resources for the other hyperthread
+    SpinWithPause,
+    /// Pause for twice as long after every failed attempt, which reduces the
+    /// traffic on the cache line of the lock when there is a lot of contention
+    ExponentialBackoff,
+    /// Spin with pauses for a while, then yield the core to other threads, so a
+    /// thread that holds the lock gets to run even if there are more threads than cores
+    #[default]
+    SpinThenYield,
+}
+
+/// The state of a thread that waits according to a `SpinStrategy`
+pub struct SpinWait {
+    strategy: SpinStrategy,
+    step: u32,
+}
+
+impl SpinWait {
+    pub fn new(strategy: SpinStrategy) -> SpinWait {
+        SpinWait { strategy, step: 0 }
+    }
+
+    /// Wait a single time, e.g. after a failed attempt to take a lock
+    pub fn wait(&mut self) {
+        // loom can only explore a busy loop if every iteration yields
+        if cfg!(loom) {
+            return thread::yield_now();
+        }
+
+        match self.strategy {
+            SpinStrategy::Spin => {}
+            SpinStrategy::SpinWithPause => spin_loop(),
+            SpinStrategy::ExponentialBackoff => {
+                for _ in 0..1 << self.step {
+                    spin_loop();
+                }
+                self.step = (self.step + 1).min(MAX_BACKOFF_STEP);
+            }
+            SpinStrategy::SpinThenYield => {
+                if self.step < SPIN_LIMIT {
+                    self.step += 1;
+                    spin_loop();
+                } else {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use super::{Lock, SpinLock};
+
+/// Contention statistics of a single lock, updated by every thread that uses it
+#[derive(Debug, Default)]
+pub struct LockStats {
+    acquisitions: AtomicU64,
+    failed_attempts: AtomicU64,
+    wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+    hold_nanos: AtomicU64,
+}
+
+/// A thread that started waiting for the lock
+#[derive(Clone, Copy, Debug)]
+pub struct Waiting {
+    since: Instant,
+}
+
+/// A thread that holds the lock
+#[derive(Clone, Copy, Debug)]
+pub struct Holding {
+    since: Instant,
+}
+
+impl LockStats {
+    pub fn new() -> LockStats {
+        LockStats::default()
+    }
+
+    /// Call right before taking the lock
+    pub fn start_waiting(&self) -> Waiting {
+        Waiting { since: Instant::now() }
+    }
+
+    /// Call right after taking the lock, with the amount of failed CaS
+    /// attempts while waiting, see `Lock::lock_counting`
+    pub fn acquired(&self, waiting: Waiting, failed_attempts: u64) -> Holding {
+        let now = Instant::now();
+        let wait = nanos(now - waiting.since);
+
+        // The statistics are only read after all threads are done, so there is
+        // nothing to synchronize with
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        self.failed_attempts.fetch_add(failed_attempts, Ordering::Relaxed);
+        self.wait_nanos.fetch_add(wait, Ordering::Relaxed);
+        self.max_wait_nanos.fetch_max(wait, Ordering::Relaxed);
+
+        Holding { since: now }
+    }
+
+    /// Call right before releasing the lock
+    pub fn released(&self, holding: Holding) {
+        self.hold_nanos.fetch_add(nanos(holding.since.elapsed()), Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> LockSummary {
+        LockSummary {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            failed_attempts: self.failed_attempts.load(Ordering::Relaxed),
+            wait_time: Duration::from_nanos(self.wait_nanos.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait_nanos.load(Ordering::Relaxed)),
+            hold_time: Duration::from_nanos(self.hold_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().min(u64::MAX as u128) as u64
+}
+
+/// A snapshot of `LockStats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockSummary {
+    pub acquisitions: u64,
+    /// Failed CaS attempts; always 0 for locks that don't spin on a CaS
+    pub failed_attempts: u64,
+    /// The total time that threads spent waiting for the lock
+    pub wait_time: Duration,
+    /// The longest time that a single thread waited for the lock
+    pub max_wait: Duration,
+    /// The total time that threads spent holding the lock
+    pub hold_time: Duration,
+}
+
+impl fmt::Display for LockSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} acquisitions, {} failed attempts, {:.3} ms waiting (max {:.3} ms), {:.3} ms holding",
+               self.acquisitions,
+               self.failed_attempts,
+               self.wait_time.as_secs_f64() * 1e3,
+               self.max_wait.as_secs_f64() * 1e3,
+               self.hold_time.as_secs_f64() * 1e3)
+    }
+}
+
+/// A lock that records `LockStats`
+///
+/// Any `Lock` can be instrumented, as well as the closure-based `SpinLock`.
+pub struct Instrumented<L> {
+    lock: L,
+    stats: LockStats,
+}
+
+impl<L> Instrumented<L> {
+    pub fn new(lock: L) -> Instrumented<L> {
+        Instrumented { lock, stats: LockStats::new() }
+    }
+
+    pub fn stats(&self) -> LockSummary {
+        self.stats.summary()
+    }
+}
+
+impl<T, L: Lock<T>> Lock<T> for Instrumented<L> {
+    type Guard<'a> = InstrumentedGuard<'a, L::Guard<'a>> where Self: 'a;
+
+    fn new(data: T) -> Self {
+        Instrumented::new(L::new(data))
+    }
+
+    fn lock(&self) -> LockResult<Self::Guard<'_>> {
+        let waiting = self.stats.start_waiting();
+        let mut failed_attempts = 0;
+        let result = self.lock.lock_counting(&mut failed_attempts);
+        let holding = self.stats.acquired(waiting, failed_attempts);
+
+        let instrument = |guard| InstrumentedGuard { guard, stats: &self.stats, holding };
+        match result {
+            Ok(guard) => Ok(instrument(guard)),
+            Err(e) => Err(PoisonError::new(instrument(e.into_inner()))),
+        }
+    }
+}
+
+impl<T> Instrumented<SpinLock<T>> {
+    /// Like `SpinLock::lock`, but records how long the closure waited for the
+    /// lock and how long it held it.
+    pub fn lock<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> LockResult<R> {
+        let stats = &self.stats;
+        let waiting = stats.start_waiting();
+        self.lock.lock_counting(|data, failed_attempts| {
+            let holding = stats.acquired(waiting, failed_attempts);
+            let result = f(data);
+            stats.released(holding);
+            result
+        })
+    }
+}
+
+/// The guard of an `Instrumented` lock
+pub struct InstrumentedGuard<'a, G> {
+    guard: G,
+    stats: &'a LockStats,
+    holding: Holding,
+}
+
+impl<'a, G> Drop for InstrumentedGuard<'a, G> {
+    fn drop(&mut self) {
+        // The inner guard releases the lock right after this
+        self.stats.released(self.holding);
+    }
+}
+
+impl<'a, G: Deref> Deref for InstrumentedGuard<'a, G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &G::Target {
+        &self.guard
+    }
+}
+
+impl<'a, G: DerefMut> DerefMut for InstrumentedGuard<'a, G> {
+    fn deref_mut(&mut self) -> &mut G::Target {
+        &mut self.guard
+    }
+}
@@ -0,0 +1,74 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::spin_strategy::{SpinStrategy, SpinWait};
+
+/// A fair spin lock: threads take a ticket and are served in the order in
+/// which they arrived, like at the butcher's
+pub struct TicketLock<T> {
+    /// The ticket that the next thread to arrive will take
+    next_ticket: AtomicUsize,
+    /// The ticket of the thread that holds the lock
+    now_serving: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+unsafe impl<T: Send> Send for TicketLock<T> { }
+unsafe impl<T: Send> Sync for TicketLock<T> { }
+
+impl<T> TicketLock<T> {
+    /// Creates a new TicketLock in an unlocked state ready for use.
+    pub fn new(t: T) -> TicketLock<T> {
+        TicketLock {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Acquires a TicketLockGuard, spinning the current thread until its turn comes.
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        // The ticket counters wrap around, which is fine as long as there are
+        // less than `usize::MAX` threads waiting at the same time
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let mut spin_wait = SpinWait::new(SpinStrategy::SpinThenYield);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            spin_wait.wait();
+        }
+
+        TicketLockGuard { lock: self, _marker: PhantomData }
+    }
+}
+
+pub struct TicketLockGuard<'a, T: 'a> {
+    lock: &'a TicketLock<T>,
+    /// Opts out of `Send` and `Sync`, like `MutexGuard`: a shared guard hands
+    /// out `&T`, so it may only be `Sync` if `T: Sync`, not just `T: Send`
+    _marker: PhantomData<*const ()>,
+}
+
+unsafe impl<'a, T: Sync> Sync for TicketLockGuard<'a, T> { }
+
+impl<'a, T> Drop for TicketLockGuard<'a, T> {
+    fn drop(&mut self) {
+        // Only the holder of the lock writes `now_serving`, so there is no need
+        // for an atomic increment
+        let next = self.lock.now_serving.load(Ordering::Relaxed).wrapping_add(1);
+        self.lock.now_serving.store(next, Ordering::Release);
+    }
+}
+
+impl<'a, T> Deref for TicketLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for TicketLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
@@ -1,68 +1,262 @@
-#![feature(conservative_impl_trait)]
-#![feature(optin_builtin_traits)]
-
+extern crate iban_calculator;
 extern crate rand;
-extern crate rayon;
-extern crate sha1;
 
-mod backend;
-mod lock;
-mod parse;
-mod settings;
-mod util;
+use std::env;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::process;
 
 use rand::distributions::{Range, IndependentSample};
 
-use backend::Backend;
-use parse::FromArgsError::InvalidHash;
-use settings::{Mode, Settings};
+use iban_calculator::{bench, judge, parse, validate};
+use iban_calculator::iban::{self, format, suggest, Iban};
+use iban_calculator::iban::banks::BankDirectory;
+use iban_calculator::iban::legacy::BankRanges;
+use iban_calculator::output::{OutputSink, SinkKind, DEFAULT_BUFFER_SIZE};
+use iban_calculator::backend::{with_backend, Backend, BackendFn, BACKEND_NAMES};
+use iban_calculator::parse::FromArgsError::InvalidHash;
+use iban_calculator::settings::{Mode, Settings};
+use iban_calculator::util::Checksum;
 
 /// The entry point of the appllication
 fn main() {
+    // Subcommands are passed as arguments, the assignment itself reads from stdin
+    if let Some(command) = env::args().nth(1) {
+        match &*command {
+            "judge" => {
+                let dir = env::args().nth(2).unwrap_or_else(|| "tests/fixtures".to_owned());
+                let passed = judge::run(Path::new(&dir));
+                process::exit(if passed { 0 } else { 1 });
+            }
+            "bench" => {
+                let passed = match bench::parse_args(env::args().skip(2)) {
+                    Ok(config) => bench::run(&config).unwrap(),
+                    Err(e) => panic!("{}", e)
+                };
+                process::exit(if passed { 0 } else { 1 });
+            }
+            "suggest" => {
+                // The IBAN may be passed in print format, as several arguments
+                let iban = format::normalize(&env::args().skip(2).collect::<Vec<_>>().join(" "));
+                if iban.is_empty() {
+                    panic!("suggest needs an IBAN");
+                }
+                let suggestions = suggest::suggest(&iban);
+                if suggestions.is_empty() {
+                    println!("-1");
+                }
+                for suggestion in suggestions {
+                    println!("{} ({})", format::print_format(&suggestion.iban), suggestion.edit);
+                }
+                return;
+            }
+            "lookup" => {
+                match env::args().skip(2).collect::<Vec<_>>().join(" ").parse::<Iban>() {
+                    Ok(iban) => lookup(&iban),
+                    Err(e) => {
+                        eprintln!("invalid IBAN: {}", e);
+                        println!("-1");
+                    }
+                }
+                return;
+            }
+            "validate" => {
+                let config = validate::parse_args(env::args().skip(2)).unwrap_or_else(|e| panic!("{}", e));
+                let name = backend_name();
+                if with_backend(&name, Validate { config: &config }).is_none() {
+                    panic!("unknown backend: {}", name);
+                }
+                return;
+            }
+            "convert" => {
+                convert();
+                return;
+            }
+            _ => panic!("unknown subcommand: {}", command)
+        }
+    }
+
     match parse::from_args() {
-        Ok((settings, mode)) => run_any_backend(&settings, mode),
-        Err(InvalidHash(_)) => println!("-1"),
-        Err(e) => panic!(e)
+        Ok((mut settings, mode)) => {
+            // Not part of the assignment input, so it can't be passed on stdin
+            settings.lock_stats = env::var_os("IBAN_LOCK_STATS").is_some();
+            if let Ok(size) = env::var("IBAN_CHUNK_SIZE") {
+                settings.chunk_size = size.parse().unwrap_or_else(|e| panic!("invalid IBAN_CHUNK_SIZE: {}", e));
+            }
+            if let Ok(name) = env::var("IBAN_CHECKSUM") {
+                settings.checksum = Checksum::from_name(&name).unwrap_or_else(|| panic!("unknown IBAN_CHECKSUM: {}", name));
+            }
+            run_any_backend(&settings, mode)
+        }
+        // The assignment expects -1 for an invalid hash, the reason goes to stderr
+        Err(e @ InvalidHash(..)) => {
+            eprintln!("{}", e);
+            println!("-1");
+        }
+        Err(e) => panic!("{}", e)
     }
 }
 
-/// Run the `IbanCalculator` assignment, with the given backend, settings and mode
-pub fn run<T: Backend>(_imp: T, settings: &Settings, mode: Mode) {
-    use self::Mode::*;
-    match mode {
-        Count => println!("{}", T::run_count(settings)),
-        List => T::run_list(settings),
-        Search(hash) => match T::run_search(settings, hash) {
-            Some(x) => println!("{}", x),
-            None => println!("-1")
+/// Run the `IbanCalculator` assignment, with the given settings and mode, on
+/// the backend passed to `call`
+struct Run<'a> {
+    settings: &'a Settings,
+    mode: Mode,
+}
+
+impl<'a> BackendFn for Run<'a> {
+    type Output = ();
+
+    fn call<T: Backend>(self) {
+        use self::Mode::*;
+        let settings = self.settings;
+        let width = settings.checksum.width();
+        match self.mode {
+            Count => println!("{}", T::run_count(settings)),
+            List => {
+                let mut sink = open_sink();
+                T::run_list(settings, &mut sink);
+                sink.finish().unwrap();
+            }
+            Search(hash) => match T::run_search(settings, hash) {
+                Some(x) => println!("{:01$}", x, width),
+                None => println!("-1")
+            },
+            Generate(ibans) => {
+                let mut sink = open_sink();
+                T::run_list_as(settings, &mut sink, &ibans);
+                sink.finish().unwrap();
+            }
+            PrefixSearch(prefix) => {
+                let found = T::run_prefix_search(settings, &prefix);
+                if found.is_empty() {
+                    println!("-1");
+                }
+                for x in found {
+                    println!("{:01$}", x, width);
+                }
+            }
         }
     }
 }
 
-/// Run the `IbanCalculator` assignment using any backend
-fn run_any_backend(settings: &Settings, mode: Mode) {
-    use backend::*;
-    let mut rng = rand::thread_rng();
-    let range = Range::new(0, 5);
-
-    // Note: in case you want to run a particular backend, replace the backend index
-    // by the desired integer.
-    let backend_index = range.ind_sample(&mut rng);
-    match backend_index {
-        0 => {
-            let backend = SpinLockBackend::new(settings.threads as usize);
-            run(backend, settings, mode)
+/// Print the bank of the IBAN, as its bank identifier, BIC and name
+///
+/// The directory is the bundled one, or the CSV file in `IBAN_BANKS` (see
+/// `iban::banks`). An unknown bank prints -1, like an invalid IBAN.
+fn lookup(iban: &Iban) {
+    let directory = match env::var_os("IBAN_BANKS") {
+        Some(path) => BankDirectory::load(Path::new(&path)).unwrap_or_else(|e| panic!("{}", e)),
+        None => BankDirectory::bundled(),
+    };
+    match directory.lookup(iban) {
+        Some(bank) => println!("{} {} {}", bank.bank, bank.bic, bank.name),
+        None => {
+            eprintln!("unknown bank {} in {}", iban.bank(), iban.country());
+            println!("-1");
         }
-        1 => {
-            let backend = RayonBackend::new(settings.threads as usize);
-            run(backend, settings, mode)
+    }
+
+    // The IBAN is valid, but a Dutch account was checked with the elfproef too
+    let account = &iban.bban()[4..];
+    if iban.country() == "NL" && !iban::passes_elfproef(account) {
+        eprintln!("the account {} does not pass the elfproef", account);
+    }
+}
+
+/// Validate a column of the file on stdin, see `validate`
+///
+/// The rows are written like the list output (see `open_sink`), and the summary
+/// goes to stderr.
+struct Validate<'a> {
+    config: &'a validate::ValidateConfig,
+}
+
+impl<'a> BackendFn for Validate<'a> {
+    type Output = ();
+
+    fn call<T: Backend>(self) {
+        let stdin = io::stdin();
+        let mut sink = open_sink();
+        match validate::run::<T, _, _>(self.config, stdin.lock(), &mut sink) {
+            Ok(summary) => eprint!("{}", summary),
+            Err(e) => panic!("{}", e),
         }
-        2 => {
-            let backend = AdvancedSpinLockBackend::new(settings.threads as usize);
-            run(backend, settings, mode)
+        sink.finish().unwrap();
+    }
+}
+
+/// Convert the Dutch account numbers on stdin, one per line, into IBANs
+///
+/// Every line prints the account and its IBAN, or the account and -1 when it
+/// can't be converted, with the reason on stderr. The ranges of the bank
+/// accounts are read from the CSV file in `IBAN_LEGACY_BANKS` (see
+/// `iban::legacy`); without it, only giro accounts can be converted.
+fn convert() {
+    let ranges = match env::var_os("IBAN_LEGACY_BANKS") {
+        Some(path) => BankRanges::load(Path::new(&path)).unwrap_or_else(|e| panic!("{}", e)),
+        None => {
+            eprintln!("IBAN_LEGACY_BANKS is not set, so only giro accounts can be converted");
+            BankRanges::default()
+        }
+    };
+
+    let stdin = io::stdin();
+    let mut failed = 0;
+    for (i, line) in stdin.lock().lines().enumerate() {
+        let account = line.unwrap();
+        match ranges.convert(&account) {
+            Ok(iban) => println!("{} {}", account.trim(), iban),
+            Err(e) => {
+                failed += 1;
+                eprintln!("line {}: {}", i + 1, e);
+                println!("{} -1", account.trim());
+            }
+        }
+    }
+    if failed > 0 {
+        eprintln!("{} accounts could not be converted", failed);
+    }
+}
+
+/// Open the sink for the list output
+///
+/// Like the backend, the sink and its buffer size are not part of the assignment
+/// input, so they are passed through `IBAN_OUTPUT` (e.g. `gzip:list.gz`, see
+/// `SinkKind`) and `IBAN_BUFFER_SIZE` (in bytes). By default, the output goes
+/// to stdout.
+fn open_sink() -> OutputSink {
+    let kind = match env::var("IBAN_OUTPUT") {
+        Ok(kind) => kind.parse().unwrap_or_else(|e| panic!("{}", e)),
+        Err(_) => SinkKind::Stdout,
+    };
+    let buffer_size = match env::var("IBAN_BUFFER_SIZE") {
+        Ok(size) => size.parse().unwrap_or_else(|e| panic!("invalid IBAN_BUFFER_SIZE: {}", e)),
+        Err(_) => DEFAULT_BUFFER_SIZE,
+    };
+
+    OutputSink::open(&kind, buffer_size).unwrap_or_else(|e| panic!("cannot open {:?}: {}", kind, e))
+}
+
+/// Run the `IbanCalculator` assignment using any backend
+fn run_any_backend(settings: &Settings, mode: Mode) {
+    let name = backend_name();
+    if with_backend(&name, Run { settings, mode }).is_none() {
+        panic!("unknown backend: {}", name);
+    }
+}
+
+/// The backend in `IBAN_BACKEND`, or a random one
+///
+/// Note: in case you want to run a particular backend, set the `IBAN_BACKEND`
+/// environment variable to one of the names in `BACKEND_NAMES`.
+fn backend_name() -> String {
+    match env::var("IBAN_BACKEND") {
+        Ok(name) => name,
+        Err(_) => {
+            let mut rng = rand::thread_rng();
+            let range = Range::new(0, BACKEND_NAMES.len());
+            BACKEND_NAMES[range.ind_sample(&mut rng)].to_owned()
         }
-        3 => run(ThreadBackend, settings, mode),
-        4 => run(SequentialBackend, settings, mode),
-        _ => unreachable!(),
     }
 }
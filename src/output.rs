@@ -0,0 +1,191 @@
+//! Output sinks for the list mode.
+//!
+//! Writing every line straight to stdout takes the stdout lock and flushes it
+//! once per line, which dominates the runtime when there are many matches. The
+//! backends write to any `io::Write` instead, and the caller picks an
+//! `OutputSink` that buffers the lines before they reach their destination.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[cfg(feature = "gzip")]
+use flate2::Compression;
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
+
+/// The buffer size used if the caller doesn't pick one
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Where an `OutputSink` writes to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SinkKind {
+    Stdout,
+    File(PathBuf),
+    /// A gzip compressed file, only available with the `gzip` feature
+    Gzip(PathBuf),
+    /// A zstd compressed file, only available with the `zstd` feature
+    Zstd(PathBuf),
+    /// Keep the output in memory, e.g. for tests
+    Memory,
+    /// Throw the output away, only counting the bytes, e.g. for benchmarks
+    Discard,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SinkKindError {
+    UnknownSink(String),
+    MissingPath(&'static str),
+}
+
+impl fmt::Display for SinkKindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SinkKindError::UnknownSink(ref s) => write!(f, "unknown output sink: {}", s),
+            SinkKindError::MissingPath(kind) => write!(f, "the {} output sink needs a path, e.g. `{}:out.txt`", kind, kind),
+        }
+    }
+}
+
+/// Parses `stdout`, `memory`, `discard`, `file:PATH`, `gzip:PATH` or `zstd:PATH`
+impl FromStr for SinkKind {
+    type Err = SinkKindError;
+
+    fn from_str(s: &str) -> Result<SinkKind, SinkKindError> {
+        let (kind, path) = match s.find(':') {
+            Some(i) => (&s[..i], Some(PathBuf::from(&s[i + 1..]))),
+            None => (s, None),
+        };
+        let path = |kind| path.clone().filter(|p| !p.as_os_str().is_empty()).ok_or(SinkKindError::MissingPath(kind));
+
+        match kind {
+            "stdout" => Ok(SinkKind::Stdout),
+            "memory" => Ok(SinkKind::Memory),
+            "discard" => Ok(SinkKind::Discard),
+            "file" => Ok(SinkKind::File(path("file")?)),
+            "gzip" => Ok(SinkKind::Gzip(path("gzip")?)),
+            "zstd" => Ok(SinkKind::Zstd(path("zstd")?)),
+            _ => Err(SinkKindError::UnknownSink(s.to_owned())),
+        }
+    }
+}
+
+/// A buffered destination for the output of the list mode
+///
+/// Call `finish` when done, which flushes the buffer (and finishes the
+/// compressed stream) and reports errors, unlike dropping the sink.
+pub struct OutputSink {
+    inner: Inner,
+    bytes_written: u64,
+}
+
+enum Inner {
+    Stdout(BufWriter<Stdout>),
+    File(BufWriter<File>),
+    #[cfg(feature = "gzip")]
+    Gzip(BufWriter<GzEncoder<File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(BufWriter<::zstd::Encoder<'static, File>>),
+    Memory(Vec<u8>),
+    Discard,
+}
+
+/// What is left of an `OutputSink` after `finish`
+#[derive(Debug)]
+pub struct Finished {
+    pub bytes_written: u64,
+    /// The output, if it was kept in memory
+    pub memory: Option<Vec<u8>>,
+}
+
+impl OutputSink {
+    /// Opens a sink of the given kind, which buffers up to `buffer_size` bytes
+    pub fn open(kind: &SinkKind, buffer_size: usize) -> io::Result<OutputSink> {
+        let inner = match *kind {
+            SinkKind::Stdout => Inner::Stdout(BufWriter::with_capacity(buffer_size, io::stdout())),
+            SinkKind::File(ref path) => Inner::File(BufWriter::with_capacity(buffer_size, File::create(path)?)),
+            #[cfg(feature = "gzip")]
+            SinkKind::Gzip(ref path) => {
+                let encoder = GzEncoder::new(File::create(path)?, Compression::default());
+                Inner::Gzip(BufWriter::with_capacity(buffer_size, encoder))
+            }
+            #[cfg(feature = "zstd")]
+            SinkKind::Zstd(ref path) => {
+                let encoder = ::zstd::Encoder::new(File::create(path)?, 0)?;
+                Inner::Zstd(BufWriter::with_capacity(buffer_size, encoder))
+            }
+            SinkKind::Memory => Inner::Memory(Vec::with_capacity(buffer_size)),
+            SinkKind::Discard => Inner::Discard,
+            #[allow(unreachable_patterns)]
+            _ => return Err(io::Error::new(io::ErrorKind::Unsupported,
+                                           format!("{:?} needs a feature that is not enabled", kind))),
+        };
+
+        Ok(OutputSink { inner, bytes_written: 0 })
+    }
+
+    pub fn stdout(buffer_size: usize) -> OutputSink {
+        OutputSink { inner: Inner::Stdout(BufWriter::with_capacity(buffer_size, io::stdout())), bytes_written: 0 }
+    }
+
+    pub fn memory() -> OutputSink {
+        OutputSink { inner: Inner::Memory(vec![]), bytes_written: 0 }
+    }
+
+    pub fn discard() -> OutputSink {
+        OutputSink { inner: Inner::Discard, bytes_written: 0 }
+    }
+
+    /// The amount of bytes written to the sink so far, before compression
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Flushes the sink, finishes compressed streams and returns what is left
+    pub fn finish(self) -> io::Result<Finished> {
+        let bytes_written = self.bytes_written;
+        let memory = match self.inner {
+            Inner::Stdout(mut w) => { w.flush()?; None }
+            Inner::File(w) => { w.into_inner().map_err(|e| e.into_error())?.sync_all()?; None }
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(w) => { w.into_inner().map_err(|e| e.into_error())?.finish()?.sync_all()?; None }
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(w) => { w.into_inner().map_err(|e| e.into_error())?.finish()?.sync_all()?; None }
+            Inner::Memory(v) => Some(v),
+            Inner::Discard => None,
+        };
+
+        Ok(Finished { bytes_written, memory })
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = match self.inner {
+            Inner::Stdout(ref mut w) => w.write(buf)?,
+            Inner::File(ref mut w) => w.write(buf)?,
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(ref mut w) => w.write(buf)?,
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(ref mut w) => w.write(buf)?,
+            Inner::Memory(ref mut v) => v.write(buf)?,
+            Inner::Discard => buf.len(),
+        };
+        self.bytes_written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner {
+            Inner::Stdout(ref mut w) => w.flush(),
+            Inner::File(ref mut w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(ref mut w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(ref mut w) => w.flush(),
+            Inner::Memory(_) | Inner::Discard => Ok(()),
+        }
+    }
+}
@@ -2,28 +2,57 @@
 //! to construct a `Settings` struct and a `Mode`. These are necessary for
 //! the main program to run.
 
+use std::fmt;
 use std::io::{self, BufRead};
 use std::num::ParseIntError;
 
-use settings::{Mode, Settings};
-use util;
+use digest::{self, DigestError};
+use iban::{DutchIbans, IbanError};
+use settings::{Mode, Settings, DEFAULT_CHUNK_SIZE};
+use util::Checksum;
+
+/// Marks the hash of search mode as the start of a hash, e.g. `prefix:c73`
+pub const PREFIX_MARKER: &str = "prefix:";
 
 #[derive(Debug)]
 pub enum FromArgsError {
     InvalidArgumentAmount(usize),
-    InvalidHash(String),
+    InvalidHash(String, DigestError),
+    InvalidBank(String, IbanError),
     InvalidLockNumber(i32),
+    /// A modulo of 0, which no weighted sum is a multiple of
+    InvalidModulo(u32),
     InvalidMode(i32),
+    /// A bottom or top beyond `u32::MAX`: the ranges, and so the accounts that
+    /// generate mode can produce, end there
+    TooLarge(&'static str, u64),
     ParseError(&'static str, ParseIntError)
 }
 
+impl fmt::Display for FromArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::FromArgsError::*;
+        match *self {
+            InvalidArgumentAmount(n) => write!(f, "expected 6 or 7 arguments, got {}", n),
+            InvalidHash(ref hash, ref e) => write!(f, "invalid hash {:?}: {}", hash, e),
+            InvalidBank(ref bank, ref e) => write!(f, "invalid bank code {:?}: {}", bank, e),
+            InvalidLockNumber(n) => write!(f, "invalid lock number: {}", n),
+            InvalidModulo(n) => write!(f, "invalid modulo: {}, it must be at least 1", n),
+            InvalidMode(n) => write!(f, "invalid mode: {}", n),
+            TooLarge(field, n) => write!(f, "{} {} is too large, the range ends at {} at most", field, n, u32::MAX),
+            ParseError(field, ref e) => write!(f, "could not parse {}: {}", field, e)
+        }
+    }
+}
+
 pub fn from_args() -> Result<(Settings, Mode), FromArgsError> {
     let stdin = io::stdin();
     let stdin = stdin.lock();
     let line = stdin.lines().next().unwrap().unwrap();
-    let args: Vec<_> = line.trim_right().split_whitespace().collect();
+    let args: Vec<_> = line.split_whitespace().collect();
 
-    if args.len() != 6 && args.len() != 7 {
+    // A hash may have spaces between its bytes, so it can take several arguments
+    if args.len() < 6 || (args.len() > 7 && args[5] != "2") {
         return Err(InvalidArgumentAmount(args.len()))
     }
 
@@ -35,34 +64,60 @@ pub fn from_args() -> Result<(Settings, Mode), FromArgsError> {
             Ok(n) => return Err(InvalidLockNumber(n)),
             Err(e) => return Err(ParseError("Lock", e))
         },
-        bottom: match args[1].parse() {
-            Ok(n) => n,
-            Err(e) => return Err(ParseError("Bottom", e))
-        },
-        top: match args[2].parse() {
-            Ok(n) => n,
-            Err(e) => return Err(ParseError("Top", e))
-        },
+        bottom: parse_bound("Bottom", args[1])?,
+        top: parse_bound("Top", args[2])?,
         modulo: match args[3].parse() {
+            Ok(0) => return Err(InvalidModulo(0)),
             Ok(n) => n,
             Err(e) => return Err(ParseError("Modulo", e))
         },
         threads: match args[4].parse() {
             Ok(n) => n,
             Err(e) => return Err(ParseError("Threads", e))
-        }
+        },
+        chunk_size: DEFAULT_CHUNK_SIZE,
+        lock_stats: false,
+        checksum: Checksum::MProef,
     };
 
     let program_mode = match args[5].parse() {
         Ok(0) => Mode::Count,
         Ok(1) => Mode::List,
-        Ok(2) => match util::sha1_hex_to_bytes(&args[6]) {
-            Some(hash) => Mode::Search(hash),
-            None => return Err(InvalidHash(args[6].to_owned()))
-        },
+        Ok(2) => {
+            let hash = args[6..].join(" ");
+            // Only a hash marked as a prefix is searched as the start of one,
+            // so a truncated hash is still invalid instead of matching numbers
+            let mode = match hash.strip_prefix(PREFIX_MARKER) {
+                Some(prefix) => digest::parse_prefix(prefix).map(Mode::PrefixSearch),
+                None => digest::parse(&hash).map(Mode::Search),
+            };
+            match mode {
+                Ok(mode) => mode,
+                Err(e) => return Err(InvalidHash(hash, e))
+            }
+        }
+        Ok(3) => {
+            let bank = args.get(6).cloned().unwrap_or("");
+            match DutchIbans::new(bank) {
+                Ok(ibans) => Mode::Generate(ibans),
+                Err(e) => return Err(InvalidBank(bank.to_owned(), e))
+            }
+        }
         Ok(n) => return Err(InvalidMode(n)),
         Err(e) => return Err(ParseError("Mode", e))
     };
 
     Ok((settings, program_mode))
-}
\ No newline at end of file
+}
+
+/// Parse the bottom or top of the range
+///
+/// Parsed as `u64`, so a 10 digit account above the limit gets an explicit
+/// error instead of a parse error
+fn parse_bound(field: &'static str, arg: &str) -> Result<u32, FromArgsError> {
+    match arg.parse::<u64>() {
+        Ok(n) if n > u32::MAX as u64 => Err(FromArgsError::TooLarge(field, n)),
+        Ok(n) => Ok(n as u32),
+        Err(e) => Err(FromArgsError::ParseError(field, e))
+    }
+}
@@ -1,10 +1,23 @@
+use hash::Prefix;
+use iban::DutchIbans;
+use util::Checksum;
+
+/// The default `Settings::chunk_size`
+pub const DEFAULT_CHUNK_SIZE: u32 = 4096;
+
 #[derive(Debug)]
 pub struct Settings {
     pub custom_lock: bool,
     pub bottom: u32,
     pub top: u32,
     pub modulo: u32,
-    pub threads: u32
+    pub threads: u32,
+    /// The amount of numbers that a thread of the `ThreadBackend` takes at once
+    pub chunk_size: u32,
+    /// Print contention statistics of the lock to stderr at the end of `run_list`
+    pub lock_stats: bool,
+    /// The test that the numbers need to pass, with `modulo`
+    pub checksum: Checksum,
 }
 
 #[derive(Debug)]
@@ -12,4 +25,9 @@ pub enum Mode {
     Count,
     List,
     Search(Box<[u8; 20]>),
+    /// Search with the start of a hash, which is passed as `prefix:` followed
+    /// by up to 40 hex digits (see `parse::PREFIX_MARKER`)
+    PrefixSearch(Prefix),
+    /// List the IBAN of every account that passes the m_proef, for a Dutch bank
+    Generate(DutchIbans),
 }
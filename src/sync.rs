@@ -0,0 +1,61 @@
+//! The synchronization primitives used by `SpinLock`, `AdvancedSpinLock`,
+//! `backend::ChunkCursor` and `backend::SearchResult`
+//!
+//! Building with `RUSTFLAGS="--cfg loom"` swaps them for the ones of the `loom`
+//! crate, so the tests in `tests/loom.rs` can check every interleaving of the
+//! threads, and every reordering that the memory orderings allow, instead of
+//! only the ones that happen to occur in a normal run. Only the code exercised
+//! by those tests is meant to run in a loom build.
+
+#[cfg(not(loom))]
+mod imp {
+    use std::cell;
+    use std::hint;
+
+    pub use std::sync::Mutex;
+    pub use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    pub use std::thread;
+
+    /// `std::cell::UnsafeCell`, with the closure-based API of `loom::cell::UnsafeCell`
+    ///
+    /// The closures mark where the data is accessed, so loom can check that no
+    /// two threads access it at the same time.
+    pub struct UnsafeCell<T>(cell::UnsafeCell<T>);
+
+    impl<T> UnsafeCell<T> {
+        pub fn new(data: T) -> UnsafeCell<T> {
+            UnsafeCell(cell::UnsafeCell::new(data))
+        }
+
+        pub fn into_inner(self) -> T {
+            self.0.into_inner()
+        }
+
+        #[inline]
+        pub fn with<R, F: FnOnce(*const T) -> R>(&self, f: F) -> R {
+            f(self.0.get())
+        }
+
+        #[inline]
+        pub fn with_mut<R, F: FnOnce(*mut T) -> R>(&self, f: F) -> R {
+            f(self.0.get())
+        }
+    }
+
+    /// Called while busy waiting
+    #[inline]
+    pub fn spin_loop() {
+        hint::spin_loop()
+    }
+}
+
+#[cfg(loom)]
+mod imp {
+    pub use loom::cell::UnsafeCell;
+    pub use loom::hint::spin_loop;
+    pub use loom::sync::Mutex;
+    pub use loom::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    pub use loom::thread;
+}
+
+pub use self::imp::*;
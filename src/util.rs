@@ -1,86 +1,265 @@
 //! Utility functions
 
-use std::io::Write;
-use sha1::Sha1;
+use std::ops::Range;
+
+use digest;
+use hash::Target;
 
 /// Verifies if a given number passes the m_proef test.
 ///
 /// This function is a generalized version of the function used to check
 /// IBAN numbers for validity.
+///
+/// Panics if the modulo is 0, which no number is a multiple of.
 pub fn m_proef(test: u32, modulo: u32) -> bool {
-    let mut rest: u32 = test;
-    let mut counter: u32 = 0;
-    let mut index: u32 = 1;
+    assert!(modulo != 0, "the modulo must not be 0");
+    m_proef_sum(test).is_multiple_of(modulo)
+}
+
+/// `m_proef`, computed over the decimal digits of a number
+///
+/// This also works for the numbers that don't fit a `u32`, like the 10 digit
+/// accounts of a Dutch BBAN. Returns false if `digits` is empty or has anything
+/// but digits.
+///
+/// Panics if the modulo is 0, like `m_proef`.
+pub fn m_proef_digits(digits: &str, modulo: u32) -> bool {
+    assert!(modulo != 0, "the modulo must not be 0");
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let sum: u64 = digits.bytes().rev().zip(1..).map(|(digit, weight)| (digit - b'0') as u64 * weight).sum();
+    sum.is_multiple_of(modulo as u64)
+}
+
+/// The first number with more digits than a BSN
+pub const BSN_END: u32 = 1_000_000_000;
+
+/// Verifies if a given number passes the BSN variant of the m_proef test.
+///
+/// A BSN (burgerservicenummer) has 9 digits, with weights 9 down to 2 like the
+/// m_proef, but with a weight of -1 for the last digit. Numbers with more than
+/// 9 digits never pass, and neither does 0, which isn't a BSN although its sum
+/// is divisible. `bsn_proef(x, 11)` is the check of a real BSN.
+///
+/// Panics if the modulo is 0, like `m_proef`.
+pub fn bsn_proef(test: u32, modulo: u32) -> bool {
+    assert!(modulo != 0, "the modulo must not be 0");
+    if test == 0 || test >= BSN_END {
+        return false;
+    }
+    let units = (test % 10) as i32;
+    let sum = m_proef_sum(test) as i32 - 2 * units;
+    sum.unsigned_abs().is_multiple_of(modulo)
+}
+
+/// The weighted sum of the digits of the m_proef
+fn m_proef_sum(test: u32) -> u32 {
+    let mut rest = test;
+    let mut sum = 0;
+    let mut index = 1;
     while rest != 0 {
-        counter += (rest % 10) * index;
+        sum += (rest % 10) * index;
         rest /= 10;
         index += 1;
     }
-    (counter % modulo) == 0
+    sum
 }
 
-/// Verifies if the hash of a given number matches the hash passed as a parameter
-pub fn valid_hash(x: u32, hash: &[u8; 20]) -> bool {
-    let mut sha1 = Sha1::new();
-    let mut buffer: Vec<u8> = Vec::with_capacity(9);
+/// The weights of the digits of a number, see `MProef`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Checksum {
+    /// `m_proef`: the weights 1, 2, 3, … from the last digit to the first
+    #[default]
+    MProef,
+    /// `bsn_proef`: the weights -1, 2, 3, … up to 9 digits
+    Bsn,
+}
 
-    // Turn the x into a string (the provided hash is derived from the string,
-    // not the number itself)
-    write!(buffer, "{}", x).unwrap();
+impl Checksum {
+    /// The weight of the digit that is worth `10^i`
+    fn weight(self, i: usize) -> i32 {
+        match self {
+            Checksum::Bsn if i == 0 => -1,
+            _ => i as i32 + 1,
+        }
+    }
+
+    /// The least amount of digits that a number is listed and hashed with
+    ///
+    /// A BSN always has 9 digits, so it keeps its leading zeros.
+    pub fn width(self) -> usize {
+        match self {
+            Checksum::MProef => 1,
+            Checksum::Bsn => 9,
+        }
+    }
 
-    // Calculate the sha1 and compare
-    sha1.update(&buffer);
-    sha1.digest().bytes() == *hash
+    pub fn name(self) -> &'static str {
+        match self {
+            Checksum::MProef => "m_proef",
+            Checksum::Bsn => "bsn",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Checksum> {
+        [Checksum::MProef, Checksum::Bsn].iter().cloned().find(|checksum| checksum.name() == name)
+    }
 }
 
-/// Verifies if the hash of a given number matches the hash passed as a parameter
+/// The largest weighted sum of the digits of a `u32`, which has at most 10 digits
+const MAX_WEIGHTED_SUM: usize = 9 * (1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10);
+
+/// The amount that the weighted sums are shifted by in the table of `MProef`,
+/// so the negative sums of `Checksum::Bsn` (down to -9) fit as well
+const SUM_OFFSET: i32 = 9;
+
+/// The m_proef test for a fixed modulo, for checking many numbers
 ///
-/// Note: reuses the buffer to reduce allocations
-pub fn valid_hash_fast(x: u32,
-                       hash: &[u8; 20],
-                       buffer: &mut Vec<u8>,
-                       sha1: &mut Sha1) -> bool {
-    // Turn the x into a string (the provided hash is derived from the string,
-    // not the number itself)
-    buffer.clear();
-    write!(buffer, "{}", x).unwrap();
-
-    // Calculate the sha1 and compare
-    sha1.reset();
-    sha1.update(&buffer);
-    sha1.digest().bytes() == *hash
+/// The weighted sum of the digits is at most `MAX_WEIGHTED_SUM`, so whether
+/// a sum passes is looked up in a table instead of computed with a division.
+/// The same goes for the BSN variant, with `MProef::with_checksum`.
+pub struct MProef {
+    passes: [bool; MAX_WEIGHTED_SUM + 1 + SUM_OFFSET as usize],
+    checksum: Checksum,
 }
 
-/// Transform a string of hexadecimal values to a byte string
-pub fn sha1_hex_to_bytes(hex: &str) -> Option<Box<[u8; 20]>> {
-    // Since we want to extract 20 bytes, the original string needs
-    // to provide 40 characters. However, we need to check explicitly
-    // for this because the test suite passes wrong hash in one of the tests.
-    if hex.len() != 40 {
-        return None;
+impl MProef {
+    pub fn new(modulo: u32) -> MProef {
+        MProef::with_checksum(modulo, Checksum::MProef)
     }
 
-    let mut buffer = [0; 20];
-    let bytes = hex.as_bytes();
-
-    let mut dummy_str = String::with_capacity(2);
-    for i in 0..20 {
-        // Read each pair of UTF8 hex characters as a single byte
-        let first = i * 2;
-        let second = first + 1;
-
-        // Push them into a string and parse them as a hex number
-        dummy_str.clear();
-        dummy_str.push(bytes[first] as char);
-        dummy_str.push(bytes[second] as char);
-        let byte = u8::from_str_radix(&dummy_str, 16);
-
-        // Write the resulting byte in the buffer
-        match byte {
-            Ok(b) => buffer[i] = b,
-            Err(_) => return None
+    /// Panics if the modulo is 0, like `m_proef`
+    pub fn with_checksum(modulo: u32, checksum: Checksum) -> MProef {
+        assert!(modulo != 0, "the modulo must not be 0");
+        let mut passes = [false; MAX_WEIGHTED_SUM + 1 + SUM_OFFSET as usize];
+        for (i, pass) in passes.iter_mut().enumerate() {
+            let sum = i as i32 - SUM_OFFSET;
+            *pass = sum.unsigned_abs().is_multiple_of(modulo);
         }
+        MProef { passes, checksum }
     }
 
-    Some(Box::new(buffer))
+    /// Same as `m_proef(x, modulo)`, or `bsn_proef(x, modulo)`
+    pub fn check(&self, x: u32) -> bool {
+        let mut rest = x;
+        let mut sum = SUM_OFFSET;
+        let mut i = 0;
+        while rest != 0 {
+            sum += (rest % 10) as i32 * self.checksum.weight(i);
+            rest /= 10;
+            i += 1;
+        }
+        (self.checksum != Checksum::Bsn || (x != 0 && x < BSN_END)) && self.passes[sum as usize]
+    }
+
+    /// Returns the numbers of the range that pass the test, in ascending order
+    pub fn range(&self, range: Range<u32>) -> MProefRange<'_> {
+        // 0 isn't a BSN, and neither is anything of more than 9 digits
+        let (start, end) = match self.checksum {
+            Checksum::MProef => (range.start, range.end),
+            Checksum::Bsn => (range.start.max(1), range.end.min(BSN_END)),
+        };
+
+        let mut digits = [0; 10];
+        let mut sum = SUM_OFFSET;
+        let mut rest = start;
+        for (i, digit) in digits.iter_mut().enumerate() {
+            *digit = (rest % 10) as u8;
+            sum += *digit as i32 * self.checksum.weight(i);
+            rest /= 10;
+        }
+
+        MProefRange {
+            passes: &self.passes,
+            digits,
+            unit_weight: self.checksum.weight(0),
+            sum,
+            next: start,
+            remaining: end.saturating_sub(start),
+        }
+    }
+}
+
+/// The numbers of a range that pass the m_proef test, see `MProef::range`
+///
+/// Consecutive numbers share most of their digits, so the weighted sum is
+/// updated like an odometer instead of being computed from scratch: when a
+/// digit goes up by one, the sum goes up by its weight, and when it wraps from
+/// 9 to 0, the sum goes down by 9 times its weight and the next digit goes up.
+pub struct MProefRange<'a> {
+    passes: &'a [bool; MAX_WEIGHTED_SUM + 1 + SUM_OFFSET as usize],
+    /// The digits of `next`, least significant first
+    digits: [u8; 10],
+    /// The weight of the last digit, the only one that differs between the
+    /// checksums
+    unit_weight: i32,
+    /// The weighted sum of `digits`, plus `SUM_OFFSET`
+    sum: i32,
+    next: u32,
+    remaining: u32,
+}
+
+impl<'a> MProefRange<'a> {
+    /// Moves to the next number, which must exist
+    #[inline]
+    fn advance(&mut self) {
+        self.next += 1;
+        for (i, digit) in self.digits.iter_mut().enumerate() {
+            let weight = if i == 0 { self.unit_weight } else { i as i32 + 1 };
+            if *digit < 9 {
+                *digit += 1;
+                self.sum += weight;
+                return;
+            }
+            *digit = 0;
+            self.sum -= 9 * weight;
+        }
+    }
+}
+
+impl<'a> Iterator for MProefRange<'a> {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<u32> {
+        while self.remaining != 0 {
+            let x = self.next;
+            let pass = self.passes[self.sum as usize];
+
+            // Don't move past the end, which may be `u32::MAX`
+            self.remaining -= 1;
+            if self.remaining != 0 {
+                self.advance();
+            }
+
+            if pass {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+/// Verifies if the hash of a given number matches the hash passed as a parameter
+///
+/// Note: to check many numbers against the same hash, use `hash::Target::find`
+pub fn valid_hash(x: u32, hash: &[u8; 20]) -> bool {
+    Target::new(hash).matches(x)
+}
+
+/// Transform a string of hexadecimal values to a byte string
+///
+/// Note: `digest::parse` accepts other encodings as well, and reports what is
+/// wrong with an invalid hash
+pub fn sha1_hex_to_bytes(hex: &str) -> Option<Box<[u8; 20]>> {
+    // Only plain hex digits, without the separators that `digest` allows
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    digest::parse_hex(hex).ok()
 }
@@ -0,0 +1,345 @@
+//! Validation of a column of a file, for the `validate` subcommand.
+//!
+//! The file is CSV, TSV or a plain list with a value on every line, and is
+//! streamed in batches of `BATCH_ROWS` rows. The rows of a batch are validated
+//! in parallel by a backend (see `Backend::run_map`), and written back in the
+//! order of the input, with a `valid` and a `reason` column added. A summary of
+//! the reasons why rows failed is kept for the end.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::thread;
+
+use backend::Backend;
+use csv;
+use iban::{self, IbanError};
+use settings::Settings;
+use util::{self, Checksum};
+
+/// The amount of rows that is read before they are validated
+pub const BATCH_ROWS: u32 = 64 * 1024;
+
+/// The amount of rows that a backend thread validates at a time
+const CHUNK_ROWS: u32 = 256;
+
+/// What the validated column holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// IBANs, in any of the ways that `iban::format::normalize` accepts
+    Iban,
+    /// Account numbers, which need to pass the m_proef with the modulo
+    Account(u32),
+    /// BSNs of 9 digits, or 8 without the leading zero, which need to pass
+    /// `util::bsn_proef(x, 11)`
+    Bsn,
+}
+
+/// How the rows of the file are split into columns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Tsv,
+    /// A single column, without quotes
+    Lines,
+}
+
+impl Format {
+    /// The delimiter of the columns, which is also used for the added columns
+    pub fn delimiter(self) -> char {
+        match self {
+            Format::Csv => ',',
+            Format::Tsv | Format::Lines => '\t',
+        }
+    }
+}
+
+/// The column to validate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+    /// The position of the column, counted from 1
+    Position(usize),
+    /// The name of the column in the header
+    Name(String),
+}
+
+#[derive(Debug)]
+pub struct ValidateConfig {
+    pub kind: Kind,
+    pub format: Format,
+    pub column: Column,
+    /// Whether the first row is a header, which is implied by a column `Name`
+    pub header: bool,
+    pub threads: u32,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidateArgsError {
+    MissingValue(String),
+    InvalidValue(String, String),
+    UnknownArgument(String),
+    MissingKind,
+}
+
+impl fmt::Display for ValidateArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ValidateArgsError::*;
+        match *self {
+            MissingValue(ref flag) => write!(f, "missing value for {}", flag),
+            InvalidValue(ref flag, ref value) => write!(f, "invalid value for {}: {}", flag, value),
+            UnknownArgument(ref arg) => write!(f, "unknown argument: {}", arg),
+            MissingKind => write!(f, "--kind is required"),
+        }
+    }
+}
+
+/// Parse the arguments that follow the `validate` subcommand
+///
+/// E.g. `--kind iban --format tsv --column 3`, `--kind account --modulo 11
+/// --column account` or `--kind bsn`. The format is CSV and the column the first one by
+/// default, and there are as many threads as cores. `--header` marks the first
+/// row as a header.
+pub fn parse_args<I: Iterator<Item=String>>(mut args: I) -> Result<ValidateConfig, ValidateArgsError> {
+    use self::ValidateArgsError::*;
+
+    let mut kind = None;
+    let mut modulo = 11;
+    let mut config = ValidateConfig {
+        kind: Kind::Iban,
+        format: Format::Csv,
+        column: Column::Position(1),
+        header: false,
+        threads: thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1),
+    };
+    while let Some(flag) = args.next() {
+        if flag == "--header" {
+            config.header = true;
+            continue;
+        }
+        let value = match args.next() {
+            Some(value) => value,
+            None => return Err(MissingValue(flag))
+        };
+        let invalid = || InvalidValue(flag.clone(), value.clone());
+        match &*flag {
+            "--kind" => kind = Some(value.clone()),
+            "--modulo" => modulo = value.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?,
+            "--format" => config.format = match &*value {
+                "csv" => Format::Csv,
+                "tsv" => Format::Tsv,
+                "lines" => Format::Lines,
+                _ => return Err(invalid()),
+            },
+            "--column" => config.column = match value.parse() {
+                Ok(0) => return Err(invalid()),
+                Ok(n) => Column::Position(n),
+                Err(_) => Column::Name(value.clone()),
+            },
+            "--threads" => config.threads = value.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?,
+            _ => return Err(UnknownArgument(flag))
+        }
+    }
+
+    config.kind = match kind.as_deref() {
+        Some("iban") => Kind::Iban,
+        Some("account") => Kind::Account(modulo),
+        Some("bsn") => Kind::Bsn,
+        Some(other) => return Err(InvalidValue("--kind".to_owned(), other.to_owned())),
+        None => return Err(MissingKind),
+    };
+    if let Column::Name(_) = config.column {
+        config.header = true;
+    }
+    Ok(config)
+}
+
+/// Why a row is invalid
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// The kind of failure, which the summary counts
+    pub summary: &'static str,
+    /// The failure of this row, for the `reason` column
+    pub reason: String,
+}
+
+impl Failure {
+    fn new(summary: &'static str) -> Failure {
+        Failure { summary, reason: summary.to_owned() }
+    }
+
+    fn iban(e: IbanError) -> Failure {
+        let summary = match e {
+            IbanError::UnknownCountry(_) => "unknown country",
+            IbanError::InvalidLength { .. } => "invalid length",
+            IbanError::InvalidCharacter { .. } => "invalid character",
+            IbanError::InvalidBban { .. } => "invalid BBAN",
+            IbanError::InvalidCheckDigits => "wrong check digits",
+        };
+        Failure { summary, reason: e.to_string() }
+    }
+}
+
+/// Validates the column of a row
+pub struct Validator {
+    kind: Kind,
+    format: Format,
+    /// The index of the column, from 0
+    column: usize,
+}
+
+impl Validator {
+    /// `column` counts from 0
+    pub fn new(kind: Kind, format: Format, column: usize) -> Validator {
+        Validator { kind, format, column }
+    }
+
+    pub fn check(&self, row: &str) -> Result<(), Failure> {
+        let value = match self.format {
+            Format::Lines => row.to_owned(),
+            _ => {
+                let mut fields = csv::split(row, self.format.delimiter())
+                    .map_err(|_| Failure::new("unclosed quote"))?;
+                if self.column >= fields.len() {
+                    return Err(Failure::new("missing column"));
+                }
+                fields.swap_remove(self.column)
+            }
+        };
+        self.check_value(value.trim())
+    }
+
+    pub fn check_value(&self, value: &str) -> Result<(), Failure> {
+        match self.kind {
+            Kind::Iban => iban::validate(&iban::format::normalize(value)).map_err(Failure::iban),
+            // Over the digits, since 10 digit accounts don't fit a `u32`
+            Kind::Account(modulo) => {
+                if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(Failure::new("not an account number"));
+                }
+                if !util::m_proef_digits(value, modulo) {
+                    return Err(Failure::new("fails the m_proef"));
+                }
+                Ok(())
+            }
+            Kind::Bsn => {
+                if !(value.len() == 8 || value.len() == 9) || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(Failure::new("not a BSN"));
+                }
+                if !util::bsn_proef(value.parse().unwrap(), 11) {
+                    return Err(Failure::new("fails the BSN check"));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The amount of rows that were validated, and why the invalid ones failed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub rows: u64,
+    pub valid: u64,
+    pub failures: HashMap<&'static str, u64>,
+}
+
+/// Prints the amount of valid rows, and the failures from most to least common
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} of {} rows are valid", self.valid, self.rows)?;
+        let mut failures: Vec<_> = self.failures.iter().collect();
+        failures.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (summary, count) in failures {
+            writeln!(f, "{:>10} {}", count, summary)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ValidateError {
+    Io(io::Error),
+    /// The named column isn't in the header
+    UnknownColumn(String),
+}
+
+impl fmt::Display for ValidateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidateError::Io(ref e) => write!(f, "{}", e),
+            ValidateError::UnknownColumn(ref name) => write!(f, "no column {:?} in the header", name),
+        }
+    }
+}
+
+impl From<io::Error> for ValidateError {
+    fn from(e: io::Error) -> ValidateError {
+        ValidateError::Io(e)
+    }
+}
+
+/// Validates the rows of `input` on the backend, and writes them to `output`
+/// with the `valid` and `reason` columns added
+pub fn run<T: Backend, R: BufRead, W: Write>(config: &ValidateConfig, input: R, mut output: W)
+    -> Result<Summary, ValidateError>
+{
+    let delimiter = config.format.delimiter();
+    let mut lines = input.lines();
+
+    let mut column = match config.column {
+        Column::Position(n) => n - 1,
+        Column::Name(_) => 0,
+    };
+    if config.header {
+        if let Some(header) = lines.next() {
+            let header = header?;
+            if let Column::Name(ref name) = config.column {
+                let names = csv::split(&header, delimiter).unwrap_or_default();
+                column = names.iter().position(|n| n.trim() == name)
+                    .ok_or_else(|| ValidateError::UnknownColumn(name.clone()))?;
+            }
+            writeln!(output, "{}{}valid{}reason", header, delimiter, delimiter)?;
+        }
+    }
+    let validator = Validator::new(config.kind, config.format, column);
+
+    let mut summary = Summary::default();
+    let mut batch = Vec::with_capacity(BATCH_ROWS as usize);
+    loop {
+        batch.clear();
+        for line in lines.by_ref().take(BATCH_ROWS as usize) {
+            batch.push(line?);
+        }
+        if batch.is_empty() {
+            break;
+        }
+
+        let settings = Settings {
+            custom_lock: true,
+            bottom: 0,
+            top: batch.len() as u32,
+            modulo: 1,
+            threads: config.threads,
+            chunk_size: CHUNK_ROWS,
+            lock_stats: false,
+            checksum: Checksum::MProef,
+        };
+        let results = T::run_map(&settings, |i| validator.check(&batch[i as usize]));
+
+        for (row, result) in batch.iter().zip(results) {
+            summary.rows += 1;
+            match result {
+                Ok(()) => {
+                    summary.valid += 1;
+                    writeln!(output, "{}{}true{}", row, delimiter, delimiter)?;
+                }
+                Err(failure) => {
+                    *summary.failures.entry(failure.summary).or_insert(0) += 1;
+                    let reason = csv::quote(&failure.reason, delimiter);
+                    writeln!(output, "{}{}false{}{}", row, delimiter, delimiter, reason)?;
+                }
+            }
+        }
+    }
+    output.flush()?;
+    Ok(summary)
+}
@@ -0,0 +1,260 @@
+//! Checks that all backends agree with each other and with a naive reference
+//! implementation, for randomly generated settings and hashes.
+
+extern crate iban_calculator;
+extern crate proptest;
+extern crate sha1;
+
+use proptest::prelude::*;
+use sha1::Sha1;
+
+use iban_calculator::backend::*;
+use iban_calculator::hash::Prefix;
+use iban_calculator::settings::{Settings, DEFAULT_CHUNK_SIZE};
+use iban_calculator::util::{self, Checksum};
+
+/// The amount of hex digits of the hash that the prefix search looks for, odd
+/// so the last byte is only half compared
+const PREFIX_NIBBLES: usize = 3;
+
+/// The results of running all modes on a single backend
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    count: u32,
+    list: Vec<u32>,
+    search: Option<u32>,
+    prefix_search: Vec<u32>,
+    /// `run_map` of `map`
+    map: Vec<u64>,
+}
+
+fn settings(bottom: u32, top: u32, modulo: u32, threads: u32) -> Settings {
+    Settings { custom_lock: true, bottom, top, modulo, threads, chunk_size: DEFAULT_CHUNK_SIZE, lock_stats: false, checksum: Checksum::MProef }
+}
+
+/// The function that `run_map` is run with, which doesn't depend on the m_proef
+fn map(x: u32) -> u64 {
+    x as u64 * 3
+}
+
+fn hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha1<D: ToString>(x: D) -> Box<[u8; 20]> {
+    let mut sha1 = Sha1::new();
+    sha1.update(x.to_string().as_bytes());
+    Box::new(sha1.digest().bytes())
+}
+
+/// Run all modes on the backend
+///
+/// The list output is checked for sequential counters, and the listed numbers
+/// are returned in ascending order so they can be compared between backends.
+fn outcome<T: Backend>(settings: &Settings, hash: &[u8; 20]) -> Outcome {
+    let mut out = vec![];
+    T::run_list(settings, &mut out);
+
+    let mut list = vec![];
+    for (i, line) in String::from_utf8(out).unwrap().lines().enumerate() {
+        let mut parts = line.split(' ');
+        assert_eq!(parts.next(), Some(&*(i + 1).to_string()), "bad counter in `{}`", line);
+        let number = parts.next().unwrap();
+        let x = number.parse().unwrap();
+        assert_eq!(number, format!("{:01$}", x, settings.checksum.width()), "bad number in `{}`", line);
+        list.push(x);
+        assert_eq!(parts.next(), None);
+    }
+    list.sort();
+
+    Outcome {
+        count: T::run_count(settings),
+        list,
+        search: T::run_search(settings, Box::new(*hash)),
+        prefix_search: T::run_prefix_search(settings, &Prefix::from_hex(&hex(hash)[..PREFIX_NIBBLES]).unwrap()),
+        map: T::run_map(settings, map),
+    }
+}
+
+/// The expected outcome, computed without any parallelism
+fn reference(settings: &Settings, hash: &[u8; 20]) -> Outcome {
+    let list: Vec<u32> = (settings.bottom..settings.top)
+        .filter(|&x| match settings.checksum {
+            Checksum::MProef => util::m_proef(x, settings.modulo),
+            Checksum::Bsn => util::bsn_proef(x, settings.modulo),
+        })
+        .collect();
+    let prefix = &hex(hash)[..PREFIX_NIBBLES];
+    let digest = |x: u32| sha1(format!("{:01$}", x, settings.checksum.width()));
+    Outcome {
+        count: list.len() as u32,
+        search: list.iter().cloned().find(|&x| *digest(x) == *hash),
+        prefix_search: list.iter().cloned().filter(|&x| hex(&digest(x)).starts_with(prefix)).collect(),
+        map: (settings.bottom..settings.top).map(map).collect(),
+        list,
+    }
+}
+
+/// Assert that every backend produces the reference outcome
+fn check_all_backends(settings: &Settings, hash: &[u8; 20]) {
+    let expected = reference(settings, hash);
+    assert_eq!(outcome::<SequentialBackend>(settings, hash), expected, "sequential");
+    assert_eq!(outcome::<RayonBackend>(settings, hash), expected, "rayon");
+    assert_eq!(outcome::<ThreadBackend>(settings, hash), expected, "threads");
+    assert_eq!(outcome::<SpinLockBackend>(settings, hash), expected, "spin_lock");
+    assert_eq!(outcome::<AdvancedSpinLockBackend>(settings, hash), expected, "advanced_spin_lock");
+    assert_eq!(outcome::<TicketLockBackend>(settings, hash), expected, "ticket_lock");
+    assert_eq!(outcome::<McsLockBackend>(settings, hash), expected, "mcs_lock");
+    assert_eq!(outcome::<HybridLockBackend>(settings, hash), expected, "hybrid_lock");
+    assert_eq!(outcome::<ChunkedBackend>(settings, hash), expected, "chunked");
+}
+
+proptest! {
+    #[test]
+    fn backends_agree(bottom in any::<u32>(),
+                      size in 0u32..2000,
+                      modulo in 1u32..40,
+                      threads in 0u32..20,
+                      offset in 0u32..2100,
+                      chunk_size in 1u32..300,
+                      bsn in any::<bool>()) {
+        // The searched number is usually in range, but not always
+        let top = bottom.saturating_add(size);
+        let target = bottom.saturating_add(offset);
+        let mut settings = settings(bottom, top, modulo, threads);
+        settings.chunk_size = chunk_size;
+        if bsn {
+            settings.checksum = Checksum::Bsn;
+        }
+        check_all_backends(&settings, &sha1(target));
+    }
+}
+
+#[test]
+fn empty_range() {
+    check_all_backends(&settings(1000, 1000, 11, 4), &sha1(1000));
+}
+
+#[test]
+fn reversed_range() {
+    check_all_backends(&settings(1000, 10, 1, 4), &sha1(500));
+}
+
+#[test]
+fn more_threads_than_numbers() {
+    check_all_backends(&settings(0, 10, 1, 17), &sha1(9));
+    check_all_backends(&settings(5, 6, 1, 3), &sha1(5));
+}
+
+#[test]
+fn top_at_max() {
+    check_all_backends(&settings(u32::MAX - 1000, u32::MAX, 7, 3), &sha1(u32::MAX - 1));
+    check_all_backends(&settings(u32::MAX - 1000, u32::MAX, 1, 5), &sha1(u32::MAX - 1000));
+}
+
+#[test]
+fn bsn() {
+    let mut bsns = settings(123450000, 123460000, 11, 4);
+    bsns.checksum = Checksum::Bsn;
+    check_all_backends(&bsns, &sha1(123456782));
+
+    // No BSN has more than 9 digits
+    let mut bsns = settings(999_999_000, 1_000_001_000, 11, 3);
+    bsns.checksum = Checksum::Bsn;
+    check_all_backends(&bsns, &sha1(999_999_990));
+
+    // Nor is 0 a BSN, although its sum is divisible
+    let mut bsns = settings(0, 5000, 11, 3);
+    bsns.checksum = Checksum::Bsn;
+    check_all_backends(&bsns, &sha1(0));
+}
+
+/// A BSN always has 9 digits, so one that starts with a zero is hashed and
+/// listed with it
+#[test]
+fn bsn_with_leading_zero() {
+    assert!(util::bsn_proef(12_345_672, 11));
+    let mut bsns = settings(12_340_000, 12_350_000, 11, 4);
+    bsns.checksum = Checksum::Bsn;
+    let hash = sha1("012345672");
+    assert_eq!(reference(&bsns, &hash).search, Some(12_345_672));
+    check_all_backends(&bsns, &hash);
+
+    let mut out = vec![];
+    SequentialBackend::run_list(&bsns, &mut out);
+    assert!(String::from_utf8(out).unwrap().lines().any(|line| line.ends_with(" 012345672")));
+}
+
+#[test]
+fn lock_stats_dont_change_the_output() {
+    let mut settings = settings(274856170, 274866190, 11, 4);
+    settings.lock_stats = true;
+    check_all_backends(&settings, &sha1(274856182));
+}
+
+#[test]
+fn hash_not_in_range() {
+    check_all_backends(&settings(274856170, 274856190, 11, 4), &sha1(274856191));
+}
+
+/// The smallest of two hits in the range, on the backend
+fn find_first<T: Backend>(settings: &Settings, hits: [u32; 2]) -> Option<u32> {
+    T::run_find_first(settings, |mut numbers| numbers.find(|x| hits.contains(x)))
+}
+
+/// With two hits in the range, every backend returns the smaller one, also when
+/// the larger one is at the start of a chunk and found first
+#[test]
+fn every_backend_finds_the_smallest_hit() {
+    for threads in 1..8 {
+        for &hits in &[[90_000, 120_000], [4_097, 4_096], [199_999, 12_288]] {
+            let settings = settings(0, 200_000, 1, threads);
+            let expected = Some(hits[0].min(hits[1]));
+            for _ in 0..5 {
+                assert_eq!(find_first::<SequentialBackend>(&settings, hits), expected, "sequential");
+                assert_eq!(find_first::<RayonBackend>(&settings, hits), expected, "rayon");
+                assert_eq!(find_first::<ThreadBackend>(&settings, hits), expected, "threads");
+                assert_eq!(find_first::<SpinLockBackend>(&settings, hits), expected, "spin_lock");
+                assert_eq!(find_first::<AdvancedSpinLockBackend>(&settings, hits), expected, "advanced_spin_lock");
+                assert_eq!(find_first::<TicketLockBackend>(&settings, hits), expected, "ticket_lock");
+                assert_eq!(find_first::<McsLockBackend>(&settings, hits), expected, "mcs_lock");
+                assert_eq!(find_first::<HybridLockBackend>(&settings, hits), expected, "hybrid_lock");
+                assert_eq!(find_first::<ChunkedBackend>(&settings, hits), expected, "chunked");
+            }
+        }
+    }
+}
+
+/// With several hits, the smallest one wins, whichever thread finds it first
+#[test]
+fn search_result_picks_the_smallest_hit() {
+    for threads in 1..8 {
+        let cursor = ChunkCursor::new(0, 10000, 7);
+        let result = SearchResult::new();
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| result.search(&cursor, |mut chunk| chunk.find(|x| x % 1000 == 999)));
+            }
+        });
+        assert_eq!(result.best(), Some(999));
+
+        let hits = result.into_hits();
+        assert_eq!(hits[0], 999);
+        assert!(hits.iter().all(|x| x % 1000 == 999), "{:?}", hits);
+    }
+}
+
+#[test]
+fn search_result_keeps_every_hit() {
+    let result = SearchResult::new();
+    assert_eq!(result.best(), None);
+    assert!(!result.is_settled_below(u32::MAX));
+
+    assert!(result.publish(30));
+    assert!(!result.publish(40));
+    assert!(result.publish(10));
+    assert_eq!(result.best(), Some(10));
+    assert!(result.is_settled_below(11));
+    assert!(!result.is_settled_below(10));
+    assert_eq!(result.into_hits(), vec![10, 30, 40]);
+}
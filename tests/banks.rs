@@ -0,0 +1,81 @@
+//! Checks the bank lookup in the bundled directory, and that invalid
+//! directories are reported precisely.
+
+extern crate iban_calculator;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use iban_calculator::csv::{self, UnclosedQuote};
+use iban_calculator::iban::Iban;
+use iban_calculator::iban::banks::{BankDirectory, DirectoryError};
+
+fn bic(directory: &BankDirectory, iban: &str) -> Option<String> {
+    directory.lookup(&iban.parse::<Iban>().unwrap()).map(|bank| bank.bic.clone())
+}
+
+#[test]
+fn bank_identifiers() {
+    let iban: Iban = "NL91ABNA0417164300".parse().unwrap();
+    assert_eq!(iban.bank(), "ABNA");
+    let iban: Iban = "DE89370400440532013000".parse().unwrap();
+    assert_eq!(iban.bank(), "37040044");
+    let iban: Iban = "BE68539007547034".parse().unwrap();
+    assert_eq!(iban.bank(), "539");
+}
+
+#[test]
+fn bundled() {
+    let directory = BankDirectory::bundled();
+    assert_eq!(bic(&directory, "NL91ABNA0417164300").as_deref(), Some("ABNANL2A"));
+    assert_eq!(bic(&directory, "DE89370400440532013000").as_deref(), Some("COBADEFFXXX"));
+    assert_eq!(bic(&directory, "GB82WEST12345698765432"), None);
+
+    let rabo = directory.get("NL", "RABO").unwrap();
+    assert_eq!((&*rabo.bic, &*rabo.name), ("RABONL2U", "Rabobank"));
+    assert_eq!(directory.get("DE", "10050000").unwrap().name, "Landesbank Berlin, Berliner Sparkasse");
+    assert_eq!(directory.get("XX", "RABO"), None);
+}
+
+#[test]
+fn load() {
+    let path = env::temp_dir().join(format!("iban_banks_{}.csv", std::process::id()));
+    fs::write(&path, "# Updated\ncountry,bank,bic,name\nNL,ABNA,ABNANL2AXXX,ABN AMRO\n\nGB,WEST,WESTGB22,Westminster\n").unwrap();
+    let directory = BankDirectory::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    let directory = directory.unwrap();
+    assert_eq!(directory.len(), 2);
+    assert_eq!(bic(&directory, "NL91ABNA0417164300").as_deref(), Some("ABNANL2AXXX"));
+    assert_eq!(bic(&directory, "GB82WEST12345698765432").as_deref(), Some("WESTGB22"));
+    assert_eq!(bic(&directory, "NL20INGB0001234567"), None);
+
+    let missing = Path::new("/nonexistent/banks.csv");
+    assert!(matches!(BankDirectory::load(missing), Err(DirectoryError::Unreadable(..))));
+}
+
+#[test]
+fn invalid_directories() {
+    let parse = |lines: &str| BankDirectory::parse(&format!("country,bank,bic,name\n{}", lines)).map(|d| d.len());
+    assert_eq!(parse(""), Ok(0));
+    assert_eq!(BankDirectory::parse("NL,ABNA,ABNANL2A,ABN AMRO").err(), Some(DirectoryError::MissingHeader));
+    assert_eq!(parse("NL,ABNA,ABNANL2A"), Err(DirectoryError::InvalidColumns { line: 2, columns: 3 }));
+    assert_eq!(parse("NL,ABNA,ABNANL2A,\"ABN AMRO"), Err(DirectoryError::UnclosedQuote { line: 2 }));
+    assert_eq!(parse("XX,ABNA,ABNANL2A,ABN AMRO"),
+               Err(DirectoryError::UnknownCountry { line: 2, country: "XX".to_owned() }));
+    assert_eq!(parse("NL,ABN,ABNANL2A,ABN AMRO"), Err(DirectoryError::InvalidBank { line: 2, bank: "ABN".to_owned() }));
+    assert_eq!(parse("DE,3704004A,COBADEFFXXX,Commerzbank"),
+               Err(DirectoryError::InvalidBank { line: 2, bank: "3704004A".to_owned() }));
+    assert_eq!(parse("\nNL,ABNA,ABNANL2,ABN AMRO"),
+               Err(DirectoryError::InvalidBic { line: 3, bic: "ABNANL2".to_owned() }));
+}
+
+#[test]
+fn csv_fields() {
+    assert_eq!(csv::split("a,b,,c", ','), Ok(vec!["a".into(), "b".into(), "".into(), "c".into()]));
+    assert_eq!(csv::split("\"a,b\",\"say \"\"hi\"\"\"", ','), Ok(vec!["a,b".into(), "say \"hi\"".into()]));
+    assert_eq!(csv::split("a\tb", '\t'), Ok(vec!["a".into(), "b".into()]));
+    assert_eq!(csv::split("", ','), Ok(vec!["".into()]));
+    assert_eq!(csv::split("\"a", ','), Err(UnclosedQuote));
+}
@@ -0,0 +1,46 @@
+//! Checks the benchmark harness on small ranges, so it doesn't bitrot.
+
+extern crate iban_calculator;
+
+use iban_calculator::bench::{self, BenchMode};
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(str::to_owned).collect()
+}
+
+#[test]
+fn parse_args() {
+    let config = bench::parse_args(args("--modes list --threads 4,1,2 --sizes 100 --runs 2").into_iter()).unwrap();
+    assert_eq!(config.modes, vec![BenchMode::List]);
+    assert_eq!(config.threads, vec![1, 2, 4]);
+    assert_eq!(config.sizes, vec![100]);
+    assert_eq!(config.runs, 2);
+
+    assert!(bench::parse_args(args("--backends quantum").into_iter()).is_err());
+    assert!(bench::parse_args(args("--threads 0").into_iter()).is_err());
+    assert!(bench::parse_args(args("--runs").into_iter()).is_err());
+}
+
+#[test]
+fn csv_round_trip_and_regressions() {
+    let config = bench::parse_args(args("--threads 1,2 --sizes 1000 --runs 1").into_iter()).unwrap();
+    let results = bench::run_benchmarks(&config);
+
+    // Every backend runs with every amount of threads, except sequential
+    assert_eq!(results.len(), 3 * (8 * 2 + 1));
+    assert!(results.iter().filter(|r| r.threads == 2).all(|r| r.speedup.is_some()));
+
+    let mut csv = vec![];
+    bench::write_csv(&mut csv, &results).unwrap();
+    let read = bench::read_csv(&csv[..]).unwrap();
+    assert_eq!(read.len(), results.len());
+    assert!(bench::find_regressions(&read, &results, 0.0).is_empty());
+
+    // Pretend the baseline was twice as fast
+    let mut fast = read.clone();
+    for r in &mut fast {
+        r.seconds /= 2.0;
+    }
+    assert_eq!(bench::find_regressions(&fast, &results, 0.5).len(), results.len());
+    assert!(bench::find_regressions(&fast, &results, 1.5).is_empty());
+}
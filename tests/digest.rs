@@ -0,0 +1,90 @@
+//! Checks that digests are decoded from every supported encoding, and that
+//! invalid ones are reported precisely.
+
+extern crate iban_calculator;
+
+use std::env;
+use std::fs;
+use std::io;
+
+use iban_calculator::digest::{self, DigestError, Encoding};
+use iban_calculator::util;
+
+/// The sha1 of "274856182", from the TomJudge search example
+const HEX: &str = "c736ca9048d0967a27ec3833832f7ffb571ebd2f";
+
+fn expected() -> Box<[u8; 20]> {
+    util::sha1_hex_to_bytes(HEX).unwrap()
+}
+
+#[test]
+fn hex() {
+    assert_eq!(digest::parse(HEX), Ok(expected()));
+    assert_eq!(digest::parse(&HEX.to_uppercase()), Ok(expected()));
+    assert_eq!(digest::parse(&format!("0x{}", HEX)), Ok(expected()));
+    assert_eq!(digest::parse(&format!("hex:{}", HEX)), Ok(expected()));
+
+    let bytes: Vec<_> = (0..20).map(|i| &HEX[2 * i..2 * i + 2]).collect();
+    assert_eq!(digest::parse(&bytes.join(":")), Ok(expected()));
+    assert_eq!(digest::parse(&bytes.join(" ")), Ok(expected()));
+}
+
+#[test]
+fn base64() {
+    assert_eq!(digest::parse("xzbKkEjQlnon7Dgzgy9/+1cevS8="), Ok(expected()));
+    assert_eq!(digest::parse("xzbKkEjQlnon7Dgzgy9/+1cevS8"), Ok(expected()));
+    assert_eq!(digest::parse("xzbKkEjQlnon7Dgzgy9_-1cevS8="), Ok(expected()));
+    assert_eq!(digest::parse("base64url:xzbKkEjQlnon7Dgzgy9_-1cevS8"), Ok(expected()));
+
+    // Without the characters that differ, the encoding is ambiguous
+    let other = util::sha1_hex_to_bytes("c1dfd96eea8cc2b62785275bca38ac261256e278").unwrap();
+    assert_eq!(digest::parse("base64:wd/ZbuqMwrYnhSdbyjisJhJW4ng="), Ok(other));
+}
+
+#[test]
+fn binary_file() {
+    let path = env::temp_dir().join(format!("iban_calculator_{}_digest", std::process::id()));
+    fs::write(&path, &expected()[..]).unwrap();
+    assert_eq!(digest::parse(&format!("file:{}", path.display())), Ok(expected()));
+
+    fs::write(&path, &expected()[..19]).unwrap();
+    assert_eq!(digest::parse(&format!("file:{}", path.display())),
+               Err(DigestError::InvalidLength { encoding: Encoding::Binary, length: 19, expected: 20 }));
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(digest::parse(&format!("file:{}", path.display())),
+               Err(DigestError::Unreadable(path, io::ErrorKind::NotFound)));
+}
+
+#[test]
+fn invalid_characters() {
+    let mut hex = HEX.to_owned();
+    hex.replace_range(7..8, "z");
+    let error = digest::parse(&hex).unwrap_err();
+    assert_eq!(error, DigestError::InvalidCharacter { encoding: Encoding::Hex, character: 'z', position: 7 });
+    assert_eq!(error.to_string(), "invalid hex character 'z' at position 7");
+
+    assert_eq!(digest::parse("xzbKkEjQlnon7Dgzgy9/+1ce.S8="),
+               Err(DigestError::InvalidCharacter { encoding: Encoding::Base64, character: '.', position: 24 }));
+    assert_eq!(digest::parse("base64url:xzbKkEjQlnon7Dgzgy9/+1cevS8="),
+               Err(DigestError::InvalidCharacter { encoding: Encoding::Base64Url, character: '/', position: 19 }));
+}
+
+#[test]
+fn invalid_lengths() {
+    let error = digest::parse(&HEX[..38]).unwrap_err();
+    assert_eq!(error, DigestError::InvalidLength { encoding: Encoding::Hex, length: 38, expected: 40 });
+    assert_eq!(error.to_string(), "expected 40 hex digits, got 38");
+
+    assert_eq!(digest::parse(&format!("{}00", HEX)),
+               Err(DigestError::InvalidLength { encoding: Encoding::Hex, length: 42, expected: 40 }));
+    assert_eq!(digest::parse("base64:xzbKkEjQlnon7Dgzgy9/+1cevS"),
+               Err(DigestError::InvalidLength { encoding: Encoding::Base64, length: 26, expected: 27 }));
+}
+
+#[test]
+fn prefixes() {
+    assert_eq!(digest::parse_prefix("hex:c73").map(|p| p.nibbles()), Ok(3));
+    assert_eq!(digest::parse_prefix("c7:3").map(|p| p.nibbles()), Ok(3));
+    assert_eq!(digest::parse_prefix("hex:"), Err(DigestError::InvalidPrefixLength(0)));
+}
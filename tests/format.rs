@@ -0,0 +1,66 @@
+//! Checks that IBANs are normalized from the ways people write them, and
+//! printed and masked in groups of four.
+
+extern crate iban_calculator;
+extern crate proptest;
+
+use proptest::prelude::*;
+
+use iban_calculator::iban::{Iban, IbanError};
+use iban_calculator::iban::format::{mask, normalize, print_format};
+
+#[test]
+fn normalizes() {
+    assert_eq!(normalize("NL91ABNA0417164300"), "NL91ABNA0417164300");
+    assert_eq!(normalize("NL91 ABNA 0417 1643 00"), "NL91ABNA0417164300");
+    assert_eq!(normalize(" nl91abna0417164300\n"), "NL91ABNA0417164300");
+    assert_eq!(normalize("NL91\u{a0}ABNA\u{a0}0417\u{202f}1643\t00"), "NL91ABNA0417164300");
+    assert_eq!(normalize("IBAN NL91 ABNA 0417 1643 00"), "NL91ABNA0417164300");
+    assert_eq!(normalize("iban: NL91ABNA0417164300"), "NL91ABNA0417164300");
+    assert_eq!(normalize("IBAN:NL91ABNA0417164300"), "NL91ABNA0417164300");
+    assert_eq!(normalize("IBAN : NL91 ABNA 0417 1643 00"), "NL91ABNA0417164300");
+    assert_eq!(normalize("iban\u{a0}:\u{a0}NL91ABNA0417164300"), "NL91ABNA0417164300");
+    assert_eq!(normalize("IBAN :: NL91ABNA0417164300"), ":NL91ABNA0417164300");
+
+    // Other characters are left for the validation to report
+    assert_eq!(normalize("NL91-ABNA-0417164300"), "NL91-ABNA-0417164300");
+    assert_eq!(normalize(""), "");
+}
+
+#[test]
+fn prints_in_groups_of_four() {
+    assert_eq!(print_format("NL91ABNA0417164300"), "NL91 ABNA 0417 1643 00");
+    assert_eq!(print_format("BE68539007547034"), "BE68 5390 0754 7034");
+    assert_eq!(print_format(""), "");
+}
+
+#[test]
+fn masks() {
+    assert_eq!(mask("NL91ABNA0417164300"), "NL91 **** **** **43 00");
+    assert_eq!(mask("BE68539007547034"), "BE68 **** **** 7034");
+    assert_eq!(mask("DE89370400440532013000"), "DE89 **** **** **** **30 00");
+}
+
+#[test]
+fn parses() {
+    let iban: Iban = "iban nl91 abna 0417 1643 00".parse().unwrap();
+    assert_eq!(iban.as_str(), "NL91ABNA0417164300");
+    assert_eq!(iban.country(), "NL");
+    assert_eq!(iban.check_digits(), 91);
+    assert_eq!(iban.bban(), "ABNA0417164300");
+    assert_eq!(iban.to_string(), "NL91ABNA0417164300");
+    assert_eq!(format!("{:#}", iban), "NL91 ABNA 0417 1643 00");
+    assert_eq!(iban.masked(), "NL91 **** **** **43 00");
+
+    assert_eq!("NL92 ABNA 0417 1643 00".parse::<Iban>(), Err(IbanError::InvalidCheckDigits));
+    assert_eq!("NL91-ABNA-0417164300".parse::<Iban>(),
+               Err(IbanError::InvalidCharacter { character: '-', position: 4 }));
+}
+
+proptest! {
+    #[test]
+    fn print_format_round_trips(iban in "[A-Z]{2}[0-9]{2}[A-Z0-9]{8,26}") {
+        prop_assert_eq!(normalize(&print_format(&iban)), iban.clone());
+        prop_assert_eq!(normalize(&print_format(&iban.to_lowercase())), iban);
+    }
+}
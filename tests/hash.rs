@@ -0,0 +1,106 @@
+//! Checks the SHA-1 engine of the search mode against the sha1 crate.
+
+extern crate iban_calculator;
+extern crate proptest;
+extern crate sha1;
+
+use proptest::prelude::*;
+use sha1::Sha1;
+
+use iban_calculator::digest::{DigestError, Encoding};
+use iban_calculator::hash::{self, Prefix, Target, LANES};
+
+fn sha1<D: ToString>(x: D) -> [u8; 20] {
+    let mut sha1 = Sha1::new();
+    sha1.update(x.to_string().as_bytes());
+    sha1.digest().bytes()
+}
+
+proptest! {
+    #[test]
+    fn format_agrees(x in any::<u32>()) {
+        let mut buffer = [0; 10];
+        let expected = x.to_string();
+        prop_assert_eq!(hash::format(x, &mut buffer), expected.as_bytes());
+    }
+
+    #[test]
+    fn matches_agrees(x in any::<u32>()) {
+        prop_assert!(Target::new(&sha1(x)).matches(x));
+        prop_assert!(!Target::new(&sha1(x)).matches(x.wrapping_add(1)));
+    }
+
+    #[test]
+    fn find_agrees(bottom in any::<u32>(), size in 0u32..100, offset in 0u32..120) {
+        let top = bottom.saturating_add(size);
+        let target = bottom.saturating_add(offset);
+        let expected = (bottom..top).find(|&x| x == target);
+        prop_assert_eq!(Target::new(&sha1(target)).find(bottom..top), expected);
+    }
+
+    #[test]
+    fn padded_find_agrees(target in 0u32..1_000_000_000, offset in 0u32..40) {
+        let bottom = target.saturating_sub(offset);
+        let padded = Target::new(&sha1(format!("{:09}", target))).with_width(9);
+        prop_assert_eq!(padded.find(bottom..target + 40), Some(target));
+        prop_assert!(padded.matches(target));
+        // Without the width, the number is hashed without its leading zeros
+        prop_assert_eq!(Target::new(&sha1(format!("{:09}", target))).matches(target), target >= 100_000_000);
+    }
+}
+
+/// The lanes of a batch can have numbers of different lengths
+#[test]
+fn find_across_lengths() {
+    for &target in &[0, 9, 10, 99, 100, 999_999_999, 1_000_000_000, u32::MAX] {
+        let bottom = target.saturating_sub(LANES as u32 + 3);
+        let top = target.saturating_add(LANES as u32 + 3);
+        assert_eq!(Target::new(&sha1(target)).find(bottom..=top), Some(target), "{}", target);
+    }
+}
+
+#[test]
+fn find_returns_the_first_candidate() {
+    let target = Target::new(&sha1(42));
+    assert_eq!(target.find(vec![1, 42, 3, 42]), Some(42));
+    assert_eq!(target.find(vec![]), None);
+    assert_eq!(target.find(0..42), None);
+}
+
+#[test]
+fn parse_prefixes() {
+    assert_eq!(Prefix::from_hex("c736ca9048d0967a27ec3833832f7ffb571ebd2f"), Ok(Prefix::from_digest(&sha1(274856182))));
+    assert_eq!(Prefix::from_hex("C736CA").map(|p| p.nibbles()), Ok(6));
+    assert_eq!(Prefix::from_hex("c73").map(|p| p.nibbles()), Ok(3));
+    assert_eq!(Prefix::from_hex("0xc7:36:c").map(|p| p.nibbles()), Ok(5));
+    assert_eq!(Prefix::from_hex(""), Err(DigestError::InvalidPrefixLength(0)));
+    assert_eq!(Prefix::from_hex(&"0".repeat(41)), Err(DigestError::InvalidPrefixLength(41)));
+    assert_eq!(Prefix::from_hex("c73g"),
+               Err(DigestError::InvalidCharacter { encoding: Encoding::Hex, character: 'g', position: 3 }));
+    assert_eq!(Prefix::from_hex("é"),
+               Err(DigestError::InvalidCharacter { encoding: Encoding::Hex, character: 'é', position: 0 }));
+}
+
+proptest! {
+    #[test]
+    fn prefixes_match(x in any::<u32>(), nibbles in 1usize..=40) {
+        let hex: String = sha1(x).iter().map(|b| format!("{:02x}", b)).collect();
+        let target = Target::with_prefix(Prefix::from_hex(&hex[..nibbles]).unwrap());
+        prop_assert!(target.matches(x));
+    }
+}
+
+#[test]
+fn find_all_prefix_matches() {
+    // Every digest starts with one of the 16 digits, so a single digit prefix
+    // splits the numbers between them
+    let found: Vec<Vec<u32>> = (0..16)
+        .map(|d| Target::with_prefix(Prefix::from_hex(&format!("{:x}", d)).unwrap()).find_all(0..1000))
+        .collect();
+    assert_eq!(found.iter().map(Vec::len).sum::<usize>(), 1000);
+
+    for (d, found) in found.iter().enumerate() {
+        let expected: Vec<u32> = (0..1000).filter(|&x| sha1(x)[0] >> 4 == d as u8).collect();
+        assert_eq!(*found, expected);
+    }
+}
@@ -0,0 +1,131 @@
+//! Checks the IBAN check digits against known IBANs, and that every backend
+//! generates the same Dutch IBANs.
+
+extern crate iban_calculator;
+extern crate proptest;
+
+use proptest::prelude::*;
+
+use iban_calculator::backend::*;
+use iban_calculator::iban::{self, DutchIbans, IbanError};
+use iban_calculator::iban::countries::CharKind;
+use iban_calculator::settings::{Settings, DEFAULT_CHUNK_SIZE};
+use iban_calculator::util::{self, Checksum};
+
+/// Examples from the IBAN registry and from banks
+const VALID: &[&str] = &[
+    "NL91ABNA0417164300",
+    "DE89370400440532013000",
+    "GB82WEST12345698765432",
+    "BE68539007547034",
+    "FR1420041010050500013M02606",
+    "CH9300762011623852957",
+];
+
+#[test]
+fn known_ibans_are_valid() {
+    for &valid in VALID {
+        assert_eq!(iban::validate(valid), Ok(()), "{}", valid);
+
+        let (country, bban) = (&valid[..2], &valid[4..]);
+        assert_eq!(iban::iban(country, bban).as_deref(), Ok(valid));
+        assert_eq!(iban::check_digits(country, bban), Ok(valid[2..4].parse().unwrap()));
+    }
+}
+
+#[test]
+fn invalid_ibans() {
+    assert_eq!(iban::validate("NL92ABNA0417164300"), Err(IbanError::InvalidCheckDigits));
+    assert_eq!(iban::validate("NL91ABNA0417164301"), Err(IbanError::InvalidCheckDigits));
+    assert_eq!(iban::validate("XX91ABNA0417164300"), Err(IbanError::UnknownCountry("XX".to_owned())));
+    assert_eq!(iban::validate("NL91ABNA041716430"),
+               Err(IbanError::InvalidLength { country: "NL", length: 17, expected: 18 }));
+    assert_eq!(iban::validate("NL91 ABNA0417164300"),
+               Err(IbanError::InvalidCharacter { character: ' ', position: 4 }));
+    assert_eq!(iban::validate("NLX1ABNA0417164300"),
+               Err(IbanError::InvalidCharacter { character: 'X', position: 2 }));
+    assert_eq!(iban::validate("NL91ABN00417164300"),
+               Err(IbanError::InvalidBban { country: "NL", position: 3, expected: CharKind::Letters }));
+    assert_eq!(iban::check_digits("NL", "ABNA041716430A"),
+               Err(IbanError::InvalidBban { country: "NL", position: 13, expected: CharKind::Digits }));
+    assert!(!iban::is_valid(""));
+}
+
+#[test]
+fn invalid_banks() {
+    assert!(DutchIbans::new("ABNA").is_ok());
+    assert_eq!(DutchIbans::new("abna"),
+               Err(IbanError::InvalidBban { country: "NL", position: 0, expected: CharKind::Letters }));
+    assert_eq!(DutchIbans::new("ABN"),
+               Err(IbanError::InvalidLength { country: "NL", length: 17, expected: 18 }));
+}
+
+proptest! {
+    #[test]
+    fn dutch_ibans_agree(bank in "[A-Z]{4}", account in 0u64..10_000_000_000) {
+        let ibans = DutchIbans::new(&bank).unwrap();
+        let expected = iban::iban("NL", &format!("{}{:010}", bank, account)).unwrap();
+        prop_assert_eq!(ibans.iban(account), expected);
+        prop_assert!(iban::is_valid(&ibans.iban(account)));
+    }
+
+    #[test]
+    fn elfproef_agrees(account in any::<u32>()) {
+        prop_assert_eq!(iban::passes_elfproef(&format!("{:010}", account)), util::m_proef(account, 11));
+    }
+}
+
+#[test]
+fn elfproef_of_large_accounts() {
+    assert!(iban::passes_elfproef("5000000005"));
+    assert!(iban::passes_elfproef("9999999999"));
+    assert!(!iban::passes_elfproef("5000000006"));
+    assert!(!iban::passes_elfproef(""));
+    assert!(!iban::passes_elfproef("+417164300"));
+}
+
+/// The generated IBANs of the backend, sorted
+fn generate<T: Backend>(settings: &Settings, ibans: &DutchIbans) -> Vec<String> {
+    let mut out = vec![];
+    T::run_list_as(settings, &mut out, ibans);
+
+    let mut generated = vec![];
+    for (i, line) in String::from_utf8(out).unwrap().lines().enumerate() {
+        let mut parts = line.split(' ');
+        assert_eq!(parts.next(), Some(&*(i + 1).to_string()), "bad counter in `{}`", line);
+        generated.push(parts.next().unwrap().to_owned());
+        assert_eq!(parts.next(), None);
+    }
+    generated.sort();
+    generated
+}
+
+#[test]
+fn backends_generate_the_same_ibans() {
+    let ibans = DutchIbans::new("INGB").unwrap();
+    let settings = Settings {
+        custom_lock: true,
+        bottom: 417160000,
+        top: 417170000,
+        modulo: 11,
+        threads: 3,
+        chunk_size: DEFAULT_CHUNK_SIZE,
+        lock_stats: false,
+        checksum: Checksum::MProef,
+    };
+    let mut expected: Vec<_> = (settings.bottom..settings.top)
+        .filter(|&x| util::m_proef(x, 11))
+        .map(|x| iban::iban("NL", &format!("INGB{:010}", x)).unwrap())
+        .collect();
+    expected.sort();
+
+    assert_eq!(generate::<SequentialBackend>(&settings, &ibans), expected, "sequential");
+    assert_eq!(generate::<RayonBackend>(&settings, &ibans), expected, "rayon");
+    assert_eq!(generate::<ThreadBackend>(&settings, &ibans), expected, "threads");
+    assert_eq!(generate::<SpinLockBackend>(&settings, &ibans), expected, "spin_lock");
+    assert_eq!(generate::<AdvancedSpinLockBackend>(&settings, &ibans), expected, "advanced_spin_lock");
+    assert_eq!(generate::<TicketLockBackend>(&settings, &ibans), expected, "ticket_lock");
+    assert_eq!(generate::<McsLockBackend>(&settings, &ibans), expected, "mcs_lock");
+    assert_eq!(generate::<HybridLockBackend>(&settings, &ibans), expected, "hybrid_lock");
+    assert_eq!(generate::<ChunkedBackend>(&settings, &ibans), expected, "chunked");
+}
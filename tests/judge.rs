@@ -0,0 +1,35 @@
+//! Runs the fixtures in `tests/fixtures` against every backend through the
+//! `judge` subcommand, so the DomJudge test cases run on any platform, and
+//! checks the inputs that the program refuses instead of answering.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn fixtures_pass_on_all_backends() {
+    let output = Command::new(env!("CARGO_BIN_EXE_iban_calculator"))
+        .arg("judge")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"))
+        .output()
+        .unwrap();
+
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "judge reported failures:\n{}", report);
+}
+
+#[test]
+fn modulo_zero_is_rejected() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_iban_calculator"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"0 1 100 0 2 0\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid modulo: 0"), "unexpected stderr:\n{}", stderr);
+}
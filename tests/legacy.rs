@@ -0,0 +1,99 @@
+//! Checks the conversion of Dutch account numbers from before the IBAN.
+
+extern crate iban_calculator;
+extern crate proptest;
+
+use proptest::prelude::*;
+
+use iban_calculator::iban::{self, DutchIbans};
+use iban_calculator::iban::legacy::{BankRanges, LegacyError, TableError};
+use iban_calculator::util;
+
+fn ranges() -> BankRanges {
+    BankRanges::parse("# Test ranges\nfirst,last,bank\n400000000,499999999,ABNA\n\
+                       417000000,417999999,ABNA\n450000000,459999999,RABO\n").unwrap()
+}
+
+fn convert(account: &str) -> Result<String, LegacyError> {
+    ranges().convert(account).map(|iban| iban.to_string())
+}
+
+#[test]
+fn bank_accounts() {
+    assert_eq!(convert("417164300").as_deref(), Ok("NL91ABNA0417164300"));
+    assert_eq!(convert("0417164300").as_deref(), Ok("NL91ABNA0417164300"));
+    assert_eq!(convert(" 41.71.64.300 ").as_deref(), Ok("NL91ABNA0417164300"));
+    assert_eq!(convert("417164301"), Err(LegacyError::FailsElfproef(417164301)));
+    assert_eq!(convert("123456789"), Err(LegacyError::UnknownBank(123456789)));
+}
+
+#[test]
+fn accounts_that_dont_fit_a_u32() {
+    let mut ranges = ranges();
+    ranges.insert(5_000_000_000, 5_999_999_999, DutchIbans::new("RABO").unwrap());
+    let iban = ranges.convert("5000000005").unwrap();
+    assert!(iban::is_valid(iban.as_str()));
+    assert_eq!(iban.bban(), "RABO5000000005");
+
+    assert_eq!(ranges.convert("5000000006").map(|iban| iban.to_string()),
+               Err(LegacyError::FailsElfproef(5000000006)));
+    assert_eq!(convert("9999999999"), Err(LegacyError::UnknownBank(9999999999)));
+}
+
+#[test]
+fn bank_accounts_need_a_table() {
+    // Without ranges, the bank of a valid bank account can't be known
+    assert_eq!(BankRanges::default().convert("417164300").map(|iban| iban.to_string()),
+               Err(LegacyError::NoRanges(417164300)));
+    assert_eq!(BankRanges::parse("first,last,bank\n").unwrap().convert("417164300").map(|iban| iban.to_string()),
+               Err(LegacyError::NoRanges(417164300)));
+    assert_eq!(BankRanges::default().convert("417164301").map(|iban| iban.to_string()),
+               Err(LegacyError::FailsElfproef(417164301)));
+}
+
+#[test]
+fn giro_accounts() {
+    assert_eq!(convert("1234567").as_deref(), Ok("NL20INGB0001234567"));
+    assert_eq!(convert("P 1234567"), Err(LegacyError::InvalidCharacter { character: 'P', position: 0 }));
+    // Giro accounts don't need the elfproef, or a range
+    assert_eq!(BankRanges::default().convert("0001234567").map(|iban| iban.to_string()).as_deref(),
+               Ok("NL20INGB0001234567"));
+}
+
+#[test]
+fn invalid_lengths() {
+    assert_eq!(convert(""), Err(LegacyError::InvalidLength(0)));
+    assert_eq!(convert("0000000000"), Err(LegacyError::InvalidLength(0)));
+    assert_eq!(convert("12345678"), Err(LegacyError::InvalidLength(8)));
+    assert_eq!(convert("12345678901"), Err(LegacyError::InvalidLength(11)));
+}
+
+#[test]
+fn overlapping_ranges_are_ambiguous() {
+    let account = util::MProef::new(11).range(450000000..460000000).next().unwrap();
+    assert_eq!(ranges().convert(&account.to_string()),
+               Err(LegacyError::Ambiguous { account: account as u64, banks: vec!["ABNA".to_owned(), "RABO".to_owned()] }));
+}
+
+#[test]
+fn invalid_tables() {
+    let parse = |lines: &str| BankRanges::parse(&format!("first,last,bank\n{}", lines)).map(|_| ());
+    assert_eq!(BankRanges::parse("1,2,ABNA").err(), Some(TableError::MissingHeader));
+    assert_eq!(parse("1,2"), Err(TableError::InvalidColumns { line: 2, columns: 2 }));
+    assert_eq!(parse("1,x,ABNA"), Err(TableError::InvalidRange { line: 2 }));
+    assert_eq!(parse("2,1,ABNA"), Err(TableError::InvalidRange { line: 2 }));
+    assert_eq!(parse("1,2,abna"), Err(TableError::InvalidBank { line: 2, bank: "abna".to_owned() }));
+}
+
+proptest! {
+    #[test]
+    fn converted_accounts_are_valid(start in 100_000_000u32..999_000_000) {
+        let account = util::MProef::new(11).range(start..u32::MAX).next().unwrap();
+        let mut ranges = BankRanges::default();
+        ranges.insert(0, 9_999_999_999, DutchIbans::new("RABO").unwrap());
+
+        let iban = ranges.convert(&account.to_string()).unwrap();
+        prop_assert!(iban::is_valid(iban.as_str()));
+        prop_assert_eq!(iban.bban(), format!("RABO{:010}", account));
+    }
+}
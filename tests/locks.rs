@@ -0,0 +1,546 @@
+//! Contention tests for the locks: many threads update shared data at the same
+//! time, and the result is only correct if the lock provides mutual exclusion.
+
+extern crate iban_calculator;
+
+use std::cell::Cell;
+use std::panic;
+use std::sync::{Barrier, TryLockError};
+use std::thread;
+use std::time::Duration;
+
+use iban_calculator::lock::{kind, AdvancedSpinLock, Instrumented, Lock, LockKind, McsLock, RwSpinLock, SpinLock, SpinStrategy};
+
+const THREADS: usize = 8;
+const ITERATIONS: usize = 10_000;
+
+const STRATEGIES: [SpinStrategy; 4] = [
+    SpinStrategy::Spin,
+    SpinStrategy::SpinWithPause,
+    SpinStrategy::ExponentialBackoff,
+    SpinStrategy::SpinThenYield,
+];
+
+/// Two counters that are only equal outside of the critical section
+struct Pair {
+    a: usize,
+    b: usize,
+}
+
+impl Pair {
+    fn new() -> Pair {
+        Pair { a: 0, b: 0 }
+    }
+
+    /// Update the counters in two separate steps, so any thread that enters the
+    /// critical section at the same time sees them unequal
+    ///
+    /// Yielding in between makes it likely that the holder of the lock gets
+    /// descheduled, like it would in a run with more threads than cores.
+    fn update(&mut self, yield_in_between: bool) {
+        assert_eq!(self.a, self.b);
+        self.a += 1;
+        if yield_in_between {
+            thread::yield_now();
+        }
+        self.b += 1;
+    }
+
+    fn assert_total(&self, iterations: usize) {
+        assert_eq!(self.a, THREADS * iterations);
+        assert_eq!(self.b, THREADS * iterations);
+    }
+}
+
+/// Hammer the lock from many threads at once
+fn contend_on<L: Lock<Pair>>(lock: &L, iterations: usize, yield_in_between: bool) {
+    let barrier = Barrier::new(THREADS);
+
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                barrier.wait();
+                for _ in 0..iterations {
+                    lock.lock().unwrap().update(yield_in_between);
+                }
+            });
+        }
+    });
+
+    lock.lock().unwrap().assert_total(iterations);
+}
+
+fn kind_lock<K: LockKind>() -> K::Lock<Pair> {
+    K::Lock::new(Pair::new())
+}
+
+fn contend<K: LockKind>() {
+    contend_on(&kind_lock::<K>(), ITERATIONS, false);
+}
+
+fn contend_on_spin_lock(lock: SpinLock<Pair>, iterations: usize, yield_in_between: bool) {
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..iterations {
+                    lock.lock(|pair| pair.update(yield_in_between)).unwrap();
+                }
+            });
+        }
+    });
+
+    lock.lock(|pair| pair.assert_total(iterations)).unwrap();
+}
+
+#[test]
+fn std_mutex_under_contention() {
+    contend::<kind::StdMutex>();
+}
+
+#[test]
+fn advanced_spin_lock_under_contention() {
+    contend::<kind::AdvancedSpinLock>();
+}
+
+#[test]
+fn ticket_lock_under_contention() {
+    contend::<kind::TicketLock>();
+}
+
+#[test]
+fn mcs_lock_under_contention() {
+    contend::<kind::McsLock>();
+}
+
+#[test]
+fn hybrid_lock_under_contention() {
+    contend::<kind::HybridLock>();
+}
+
+#[test]
+fn spin_lock_under_contention() {
+    contend_on_spin_lock(SpinLock::new(Pair::new()), ITERATIONS, false);
+}
+
+#[test]
+fn spin_strategies_under_contention() {
+    for &strategy in STRATEGIES.iter() {
+        contend_on_spin_lock(SpinLock::with_strategy(Pair::new(), strategy), ITERATIONS, false);
+        contend_on(&AdvancedSpinLock::with_strategy(Pair::new(), strategy), ITERATIONS, false);
+    }
+}
+
+#[test]
+fn oversubscribed_locks_make_progress() {
+    // With the default strategy, threads waiting for a descheduled holder give
+    // up their core instead of spinning until they get preempted
+    contend_on_spin_lock(SpinLock::new(Pair::new()), 1_000, true);
+    contend_on(&AdvancedSpinLock::new(Pair::new()), 1_000, true);
+    contend_on(&kind_lock::<kind::TicketLock>(), 1_000, true);
+    contend_on(&kind_lock::<kind::McsLock>(), 1_000, true);
+    contend_on(&kind_lock::<kind::HybridLock>(), 1_000, true);
+}
+
+#[test]
+fn guard_releases_lock_when_dropped() {
+    fn relock<K: LockKind>() {
+        let lock = K::Lock::new(0);
+        for i in 0..3 {
+            let mut guard = lock.lock().unwrap();
+            assert_eq!(*guard, i);
+            *guard += 1;
+        }
+    }
+
+    relock::<kind::StdMutex>();
+    relock::<kind::AdvancedSpinLock>();
+    relock::<kind::TicketLock>();
+    relock::<kind::McsLock>();
+    relock::<kind::HybridLock>();
+}
+
+#[test]
+fn mcs_lock_nests_with_other_mcs_locks() {
+    // Every held McsLock needs a queue node of its own, also when the thread
+    // reuses its nodes
+    let locks: Vec<_> = (0..3).map(|_| McsLock::new(Pair::new())).collect();
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ITERATIONS / 10 {
+                    let mut guards: Vec<_> = locks.iter().map(|lock| lock.lock()).collect();
+                    for guard in &mut guards {
+                        guard.update(false);
+                    }
+                }
+            });
+        }
+    });
+    for lock in &locks {
+        let pair = lock.lock();
+        assert_eq!(pair.a, THREADS * ITERATIONS / 10);
+    }
+}
+
+/// Compiles only if `T` is not `Sync`: if it is, both impls apply and the
+/// call to `check` is ambiguous
+trait NotSync<A> {
+    fn check() {}
+}
+impl<T: ?Sized> NotSync<()> for T {}
+impl<T: ?Sized + Sync> NotSync<u8> for T {}
+
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn guards_are_only_sync_for_sync_data() {
+    // The locks are `Sync` for any `T: Send`, but a shared guard hands out
+    // `&T`, so it must not be `Sync` for data like a `Cell`
+    macro_rules! guard {
+        ($kind:ty, $data:ty) => { <<$kind as LockKind>::Lock<$data> as Lock<$data>>::Guard<'static> }
+    }
+
+    assert_sync::<guard!(kind::StdMutex, u32)>();
+    assert_sync::<guard!(kind::AdvancedSpinLock, u32)>();
+    assert_sync::<guard!(kind::TicketLock, u32)>();
+    assert_sync::<guard!(kind::HybridLock, u32)>();
+
+    <guard!(kind::StdMutex, Cell<u32>) as NotSync<_>>::check();
+    <guard!(kind::AdvancedSpinLock, Cell<u32>) as NotSync<_>>::check();
+    <guard!(kind::TicketLock, Cell<u32>) as NotSync<_>>::check();
+    <guard!(kind::McsLock, Cell<u32>) as NotSync<_>>::check();
+    <guard!(kind::HybridLock, Cell<u32>) as NotSync<_>>::check();
+}
+
+#[test]
+fn spin_lock_returns_closure_result() {
+    let lock = SpinLock::new(vec![1, 2]);
+
+    // Move data into the critical section and get a result out
+    let pushed = vec![3, 4];
+    let len = lock.lock(move |v| { v.extend(pushed); v.len() }).unwrap();
+    assert_eq!(len, 4);
+
+    // Mutate captured state
+    let mut sum = 0;
+    lock.lock(|v| sum = v.iter().sum()).unwrap();
+    assert_eq!(sum, 10);
+
+    // Hand out distinct indices from many threads
+    let counter = SpinLock::new(0);
+    let mut indices: Vec<usize> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..THREADS).map(|_| scope.spawn(|| {
+            (0..ITERATIONS).map(|_| counter.lock(|c| { *c += 1; *c }).unwrap()).collect::<Vec<_>>()
+        })).collect();
+        workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+    });
+    indices.sort();
+    assert_eq!(indices, (1..=THREADS * ITERATIONS).collect::<Vec<_>>());
+}
+
+#[test]
+fn try_lock_fails_while_locked() {
+    let lock = AdvancedSpinLock::new(0);
+    {
+        let _guard = lock.lock().unwrap();
+        assert!(matches!(lock.try_lock(), Err(TryLockError::WouldBlock)));
+        assert!(matches!(lock.try_lock_for(Duration::from_millis(10)), Err(TryLockError::WouldBlock)));
+    }
+    *lock.try_lock().unwrap() += 1;
+    *lock.try_lock_for(Duration::from_millis(10)).unwrap() += 1;
+    assert_eq!(lock.into_inner().unwrap(), 2);
+
+    let lock = SpinLock::new(0);
+    lock.lock(|_| {
+        assert!(matches!(lock.try_lock(|_| ()), Err(TryLockError::WouldBlock)));
+        assert!(matches!(lock.try_lock_for(Duration::from_millis(10), |_| ()), Err(TryLockError::WouldBlock)));
+    }).unwrap();
+    lock.try_lock(|x| *x += 1).unwrap();
+    lock.try_lock_for(Duration::from_millis(10), |x| *x += 1).unwrap();
+    assert_eq!(lock.into_inner().unwrap(), 2);
+}
+
+#[test]
+fn try_lock_for_waits_for_release() {
+    let lock = AdvancedSpinLock::new(0);
+    let barrier = Barrier::new(2);
+
+    thread::scope(|scope| {
+        let guard = lock.lock().unwrap();
+        scope.spawn(|| {
+            barrier.wait();
+            *lock.try_lock_for(Duration::from_secs(10)).unwrap() += 1;
+        });
+        barrier.wait();
+        thread::sleep(Duration::from_millis(10));
+        drop(guard);
+    });
+
+    assert_eq!(lock.into_inner().unwrap(), 1);
+}
+
+#[test]
+fn panicking_holder_poisons_advanced_spin_lock() {
+    let mut lock = AdvancedSpinLock::new(0);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let mut guard = lock.lock().unwrap();
+        *guard += 1;
+        panic!("holder panics");
+    }));
+    assert!(result.is_err());
+
+    // The lock was released, but everyone gets to know about the panic
+    assert!(lock.is_poisoned());
+    *lock.lock().unwrap_err().into_inner() += 1;
+    assert!(matches!(lock.try_lock(), Err(TryLockError::Poisoned(_))));
+    assert_eq!(*lock.get_mut().unwrap_err().into_inner(), 2);
+    assert_eq!(lock.into_inner().unwrap_err().into_inner(), 2);
+}
+
+#[test]
+fn panicking_closure_poisons_spin_lock() {
+    let mut lock = SpinLock::new(0);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        lock.lock(|x| {
+            *x += 1;
+            panic!("closure panics");
+        }).unwrap();
+    }));
+    assert!(result.is_err());
+
+    // Without the release on unwinding, this would spin forever
+    assert!(lock.is_poisoned());
+    assert!(lock.lock(|x| *x += 1).is_err());
+    assert!(matches!(lock.try_lock(|_| ()), Err(TryLockError::Poisoned(_))));
+    assert_eq!(*lock.get_mut().unwrap_err().into_inner(), 2);
+    assert_eq!(lock.into_inner().unwrap_err().into_inner(), 2);
+}
+
+#[test]
+fn panicking_list_worker_fails_other_workers() {
+    // Every worker panics while holding the lock; the others must fail on the
+    // poisoned lock instead of spinning forever
+    let lock = SpinLock::new(0);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..THREADS).map(|_| scope.spawn(|| {
+            lock.lock(|x| {
+                *x += 1;
+                if *x == 1 {
+                    panic!("first worker panics");
+                }
+            }).unwrap();
+        })).collect();
+
+        for worker in workers {
+            assert!(worker.join().is_err());
+        }
+    });
+}
+
+#[test]
+fn rw_spin_lock_under_contention() {
+    // Writers update the pair, readers check that they never see it halfway
+    let lock = RwSpinLock::new(Pair::new());
+    thread::scope(|scope| {
+        for i in 0..THREADS {
+            let lock = &lock;
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS / 10 {
+                    if i % 2 == 0 {
+                        lock.write().unwrap().update(false);
+                    } else {
+                        let pair = lock.read().unwrap();
+                        assert_eq!(pair.a, pair.b);
+                    }
+                }
+            });
+        }
+    });
+
+    let pair = lock.into_inner().unwrap();
+    assert_eq!(pair.a, THREADS / 2 * ITERATIONS / 10);
+    assert_eq!(pair.a, pair.b);
+}
+
+#[test]
+fn rw_spin_lock_shares_reads_and_excludes_writes() {
+    let lock = RwSpinLock::new(0);
+
+    let first = lock.read().unwrap();
+    let second = lock.try_read().unwrap();
+    assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+    drop((first, second));
+
+    let mut writer = lock.write().unwrap();
+    *writer += 1;
+    assert!(matches!(lock.try_read(), Err(TryLockError::WouldBlock)));
+    assert!(matches!(lock.try_upgradable_read(), Err(TryLockError::WouldBlock)));
+
+    // Downgrading doesn't let another writer in between
+    let reader = writer.downgrade();
+    assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+    assert_eq!(*lock.try_read().unwrap(), 1);
+    drop(reader);
+
+    assert_eq!(lock.into_inner().unwrap(), 1);
+}
+
+#[test]
+fn rw_spin_lock_upgrades_reads() {
+    let lock = RwSpinLock::new(0);
+
+    let upgradable = lock.upgradable_read().unwrap();
+    assert!(matches!(lock.try_upgradable_read(), Err(TryLockError::WouldBlock)));
+    assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+
+    // Other readers keep the upgradable guard from upgrading
+    let reader = lock.read().unwrap();
+    let upgradable = upgradable.try_upgrade().err().unwrap();
+    drop(reader);
+
+    let mut writer = upgradable.try_upgrade().ok().unwrap();
+    *writer += 1;
+    drop(writer);
+
+    // Upgrading waits for the readers that are still there
+    let barrier = Barrier::new(2);
+    thread::scope(|scope| {
+        let reader = lock.read().unwrap();
+        scope.spawn(|| {
+            let upgradable = lock.upgradable_read().unwrap();
+            barrier.wait();
+            *upgradable.upgrade() += 1;
+        });
+        barrier.wait();
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(*reader, 1);
+        drop(reader);
+    });
+
+    let upgradable = lock.upgradable_read().unwrap();
+    let reader = upgradable.downgrade();
+    drop(lock.upgradable_read().unwrap());
+    assert_eq!(*reader, 2);
+}
+
+#[test]
+fn rw_spin_lock_prefers_writers() {
+    let lock = RwSpinLock::new(0);
+    let barrier = Barrier::new(2);
+
+    thread::scope(|scope| {
+        let reader = lock.read().unwrap();
+        scope.spawn(|| {
+            barrier.wait();
+            *lock.write().unwrap() += 1;
+        });
+        barrier.wait();
+
+        // Once the writer is waiting, new readers have to wait as well
+        while lock.try_read().is_ok() {
+            thread::yield_now();
+        }
+        drop(reader);
+        assert_eq!(*lock.read().unwrap(), 1);
+    });
+}
+
+#[test]
+fn panicking_writer_poisons_rw_spin_lock() {
+    let lock = RwSpinLock::new(0);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let _reader = lock.read().unwrap();
+        panic!("reader panics");
+    }));
+    assert!(result.is_err());
+    assert!(!lock.is_poisoned());
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let mut writer = lock.write().unwrap();
+        *writer += 1;
+        panic!("writer panics");
+    }));
+    assert!(result.is_err());
+    assert!(lock.is_poisoned());
+    assert!(lock.read().is_err());
+    assert_eq!(lock.into_inner().unwrap_err().into_inner(), 1);
+}
+
+#[test]
+fn instrumented_locks_under_contention() {
+    fn contend_instrumented<K: LockKind>() {
+        let lock = Instrumented::<K::Lock<Pair>>::new(kind_lock::<K>());
+        contend_on(&lock, 1_000, false);
+
+        // One more acquisition for the final check
+        let stats = lock.stats();
+        assert_eq!(stats.acquisitions, THREADS as u64 * 1_000 + 1);
+        assert!(stats.max_wait <= stats.wait_time);
+    }
+
+    contend_instrumented::<kind::StdMutex>();
+    contend_instrumented::<kind::AdvancedSpinLock>();
+    contend_instrumented::<kind::TicketLock>();
+    contend_instrumented::<kind::McsLock>();
+    contend_instrumented::<kind::HybridLock>();
+
+    let lock = Instrumented::new(SpinLock::new(Pair::new()));
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..1_000 {
+                    lock.lock(|pair| pair.update(false)).unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(lock.stats().acquisitions, THREADS as u64 * 1_000);
+}
+
+#[test]
+fn instrumented_lock_measures_waiting_and_holding() {
+    const HOLD: Duration = Duration::from_millis(20);
+
+    let lock = Instrumented::new(AdvancedSpinLock::new(0));
+    let barrier = Barrier::new(2);
+    thread::scope(|scope| {
+        let mut guard = lock.lock().unwrap();
+        scope.spawn(|| {
+            barrier.wait();
+            *lock.lock().unwrap() += 1;
+        });
+        barrier.wait();
+        thread::sleep(HOLD);
+        *guard += 1;
+    });
+
+    let stats = lock.stats();
+    assert_eq!(stats.acquisitions, 2);
+    assert!(stats.failed_attempts > 0);
+    assert!(stats.hold_time >= HOLD);
+    assert!(stats.max_wait > Duration::ZERO);
+}
+
+#[test]
+fn failed_attempts_are_counted_per_instrumented_lock() {
+    // A thread that fails on a lock that isn't instrumented doesn't add
+    // failed attempts to the next instrumented lock that it takes
+    let plain = AdvancedSpinLock::new(0);
+    let instrumented = Instrumented::new(AdvancedSpinLock::new(0));
+    let barrier = Barrier::new(2);
+    thread::scope(|scope| {
+        let guard = plain.lock().unwrap();
+        scope.spawn(|| {
+            barrier.wait();
+            *plain.lock().unwrap() += 1;
+            *instrumented.lock().unwrap() += 1;
+        });
+        barrier.wait();
+        thread::sleep(Duration::from_millis(20));
+        drop(guard);
+    });
+
+    let stats = instrumented.stats();
+    assert_eq!(stats.acquisitions, 1);
+    assert_eq!(stats.failed_attempts, 0);
+}
@@ -0,0 +1,136 @@
+//! Model-checked tests for the spin locks, and the scheduler and search results
+//! of the `ThreadBackend`.
+//!
+//! loom runs every test many times, until it has seen every interleaving of the
+//! threads and every value that the memory orderings allow a load to return.
+//! Accessing the data of a lock without holding it is reported as a data race,
+//! so a lock that is released too early, or with too weak an ordering, fails.
+//!
+//! The tests only exist in a loom build:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+#![cfg(loom)]
+
+extern crate iban_calculator;
+extern crate loom;
+
+use std::sync::TryLockError;
+
+use loom::sync::Arc;
+use loom::thread;
+
+use iban_calculator::backend::{ChunkCursor, SearchResult};
+use iban_calculator::lock::{AdvancedSpinLock, SpinLock};
+
+/// Increment a plain, non-atomic counter from two threads
+#[test]
+fn spin_lock_mutual_exclusion() {
+    loom::model(|| {
+        let lock = Arc::new(SpinLock::new(0));
+
+        let other = lock.clone();
+        let thread = thread::spawn(move || {
+            other.lock(|x| *x += 1).unwrap();
+        });
+        lock.lock(|x| *x += 1).unwrap();
+        thread.join().unwrap();
+
+        assert_eq!(lock.lock(|x| *x).unwrap(), 2);
+    });
+}
+
+#[test]
+fn advanced_spin_lock_mutual_exclusion() {
+    loom::model(|| {
+        let lock = Arc::new(AdvancedSpinLock::new(0));
+
+        let other = lock.clone();
+        let thread = thread::spawn(move || {
+            *other.lock().unwrap() += 1;
+        });
+        *lock.lock().unwrap() += 1;
+        thread.join().unwrap();
+
+        assert_eq!(*lock.lock().unwrap(), 2);
+    });
+}
+
+/// The data written while holding a guard is visible to the next holder,
+/// but not before the guard is dropped
+#[test]
+fn advanced_spin_lock_guard_drop() {
+    loom::model(|| {
+        let lock = Arc::new(AdvancedSpinLock::new((0, 0)));
+
+        let other = lock.clone();
+        let thread = thread::spawn(move || {
+            let mut guard = other.lock().unwrap();
+            guard.0 = 1;
+            thread::yield_now();
+            guard.1 = 1;
+        });
+
+        // Either thread may go first, but never in between the two writes
+        let (a, b) = *lock.lock().unwrap();
+        assert_eq!(a, b);
+
+        thread.join().unwrap();
+        assert_eq!(*lock.lock().unwrap(), (1, 1));
+    });
+}
+
+/// A successful `try_lock` synchronizes with the previous holder like `lock`
+#[test]
+fn try_lock_memory_ordering() {
+    loom::model(|| {
+        let lock = Arc::new(AdvancedSpinLock::new(0));
+
+        let other = lock.clone();
+        let thread = thread::spawn(move || {
+            *other.lock().unwrap() = 1;
+        });
+
+        match lock.try_lock() {
+            Ok(guard) => assert!(*guard == 0 || *guard == 1),
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Poisoned(_)) => panic!("poisoned"),
+        }
+
+        thread.join().unwrap();
+        assert_eq!(*lock.try_lock().unwrap(), 1);
+    });
+}
+
+/// Every number is handed out exactly once, whatever the interleaving
+#[test]
+fn chunk_cursor_hands_out_every_number_once() {
+    loom::model(|| {
+        let cursor = Arc::new(ChunkCursor::new(u32::MAX - 5, u32::MAX, 2));
+
+        let other = cursor.clone();
+        let thread = thread::spawn(move || other.chunks().flatten().collect::<Vec<_>>());
+        let mut numbers: Vec<_> = cursor.chunks().flatten().collect();
+        numbers.extend(thread.join().unwrap());
+
+        numbers.sort();
+        assert_eq!(numbers, (u32::MAX - 5..u32::MAX).collect::<Vec<_>>());
+    });
+}
+
+/// Both threads find a hit, and the smallest one wins in every interleaving
+#[test]
+fn search_result_picks_the_smallest_hit() {
+    loom::model(|| {
+        let cursor = Arc::new(ChunkCursor::new(0, 4, 1));
+        let result = Arc::new(SearchResult::new());
+
+        let (other_cursor, other_result) = (cursor.clone(), result.clone());
+        let thread = thread::spawn(move || other_result.search(&other_cursor, |mut chunk| chunk.find(|x| x % 2 == 1)));
+        result.search(&cursor, |mut chunk| chunk.find(|x| x % 2 == 1));
+        thread.join().unwrap();
+
+        assert_eq!(result.best(), Some(1));
+    });
+}
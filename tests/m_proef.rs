@@ -0,0 +1,96 @@
+//! Checks the incremental m_proef against the plain one.
+
+extern crate iban_calculator;
+extern crate proptest;
+
+use proptest::prelude::*;
+
+use iban_calculator::util::{bsn_proef, m_proef, Checksum, MProef};
+
+fn expected(bottom: u32, top: u32, modulo: u32) -> Vec<u32> {
+    (bottom..top).filter(|&x| m_proef(x, modulo)).collect()
+}
+
+proptest! {
+    #[test]
+    fn range_agrees(bottom in any::<u32>(), size in 0u32..3000, modulo in 1u32..60) {
+        let top = bottom.saturating_add(size);
+        let m_proef = MProef::new(modulo);
+        prop_assert_eq!(m_proef.range(bottom..top).collect::<Vec<_>>(), expected(bottom, top, modulo));
+    }
+
+    #[test]
+    fn check_agrees(x in any::<u32>(), modulo in 1u32..60) {
+        prop_assert_eq!(MProef::new(modulo).check(x), m_proef(x, modulo));
+    }
+}
+
+#[test]
+fn carries_over_every_digit() {
+    for &(bottom, top) in &[(0, 10_000), (999_999_990, 1_000_000_010), (u32::MAX - 20, u32::MAX)] {
+        for &modulo in &[1, 7, 11] {
+            assert_eq!(MProef::new(modulo).range(bottom..top).collect::<Vec<_>>(), expected(bottom, top, modulo));
+        }
+    }
+}
+
+#[test]
+fn empty_and_reversed_ranges() {
+    let m_proef = MProef::new(1);
+    assert_eq!(m_proef.range(5..5).next(), None);
+    #[allow(clippy::reversed_empty_ranges)]
+    let reversed = 10..5;
+    assert_eq!(m_proef.range(reversed).next(), None);
+    assert_eq!(m_proef.range(u32::MAX..u32::MAX).next(), None);
+}
+
+proptest! {
+    #[test]
+    fn bsn_range_agrees(bottom in 0u32..1_100_000_000, size in 0u32..3000, modulo in 1u32..60) {
+        let top = bottom.saturating_add(size);
+        let expected: Vec<_> = (bottom..top).filter(|&x| bsn_proef(x, modulo)).collect();
+        let bsn = MProef::with_checksum(modulo, Checksum::Bsn);
+        prop_assert_eq!(bsn.range(bottom..top).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn bsn_check_agrees(x in any::<u32>(), modulo in 1u32..60) {
+        prop_assert_eq!(MProef::with_checksum(modulo, Checksum::Bsn).check(x), bsn_proef(x, modulo));
+    }
+}
+
+#[test]
+fn bsns() {
+    // 9*1 + 8*1 + 7*1 + 6*2 + 5*2 + 4*2 + 3*3 + 2*3 - 1*3 = 66
+    assert!(bsn_proef(111222333, 11));
+    assert!(bsn_proef(123456782, 11));
+    assert!(!bsn_proef(123456789, 11));
+    // The last digit counts negatively, which the m_proef can't express
+    assert!(!m_proef(123456782, 11));
+    // With a leading zero, a BSN has 8 digits
+    assert!(bsn_proef(10000008, 11));
+    // The sum of 0 is divisible, but 0 isn't a BSN
+    assert!(!bsn_proef(0, 11));
+    assert!(!MProef::with_checksum(11, Checksum::Bsn).check(0));
+    assert_eq!(MProef::with_checksum(1, Checksum::Bsn).range(0..3).collect::<Vec<_>>(), vec![1, 2]);
+    assert!(MProef::new(11).check(0));
+    assert!(!bsn_proef(1_000_000_002, 1));
+    assert!(!MProef::with_checksum(1, Checksum::Bsn).check(u32::MAX));
+    assert!(MProef::new(1).check(u32::MAX));
+
+    assert_eq!(Checksum::from_name("bsn"), Some(Checksum::Bsn));
+    assert_eq!(Checksum::from_name("m_proef"), Some(Checksum::MProef));
+    assert_eq!(Checksum::from_name("elfproef"), None);
+}
+
+#[test]
+#[should_panic(expected = "the modulo must not be 0")]
+fn modulo_zero_panics() {
+    m_proef(11, 0);
+}
+
+#[test]
+#[should_panic(expected = "the modulo must not be 0")]
+fn modulo_zero_panics_in_the_table() {
+    MProef::with_checksum(0, Checksum::Bsn);
+}
@@ -0,0 +1,104 @@
+//! Checks that every output sink receives exactly what the backends list.
+
+extern crate iban_calculator;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+
+use std::env;
+use std::fs;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use std::io::Read;
+use std::path::PathBuf;
+
+use iban_calculator::backend::{Backend, ChunkedBackend, RayonBackend, SequentialBackend};
+use iban_calculator::output::{OutputSink, SinkKind, SinkKindError};
+use iban_calculator::settings::{Settings, DEFAULT_CHUNK_SIZE};
+use iban_calculator::util::Checksum;
+
+fn settings() -> Settings {
+    Settings { custom_lock: true, bottom: 123456789, top: 123556789, modulo: 21, threads: 4, chunk_size: DEFAULT_CHUNK_SIZE, lock_stats: false, checksum: Checksum::MProef }
+}
+
+/// The expected list output, as produced by the sequential backend
+fn expected() -> Vec<u8> {
+    let mut out = vec![];
+    SequentialBackend::run_list(&settings(), &mut out);
+    out
+}
+
+/// List to a sink of the given kind, with a tiny buffer so it gets flushed often
+fn list_to<T: Backend>(kind: &SinkKind) -> Option<Vec<u8>> {
+    let mut sink = OutputSink::open(kind, 16).unwrap();
+    T::run_list(&settings(), &mut sink);
+    sink.finish().unwrap().memory
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("iban_calculator_{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn parse_sink_kinds() {
+    assert_eq!("stdout".parse(), Ok(SinkKind::Stdout));
+    assert_eq!("memory".parse(), Ok(SinkKind::Memory));
+    assert_eq!("discard".parse(), Ok(SinkKind::Discard));
+    assert_eq!("file:out.txt".parse(), Ok(SinkKind::File("out.txt".into())));
+    assert_eq!("gzip:C:/out.gz".parse(), Ok(SinkKind::Gzip("C:/out.gz".into())));
+    assert_eq!("zstd:out.zst".parse(), Ok(SinkKind::Zstd("out.zst".into())));
+    assert_eq!("file".parse::<SinkKind>(), Err(SinkKindError::MissingPath("file")));
+    assert_eq!("gzip:".parse::<SinkKind>(), Err(SinkKindError::MissingPath("gzip")));
+    assert_eq!("printer".parse::<SinkKind>(), Err(SinkKindError::UnknownSink("printer".to_owned())));
+}
+
+#[test]
+fn memory_sink() {
+    assert_eq!(list_to::<SequentialBackend>(&SinkKind::Memory), Some(expected()));
+    assert_eq!(list_to::<ChunkedBackend>(&SinkKind::Memory), Some(expected()));
+}
+
+#[test]
+fn discard_sink_counts_bytes() {
+    let mut sink = OutputSink::discard();
+    RayonBackend::run_list(&settings(), &mut sink);
+    assert_eq!(sink.bytes_written(), expected().len() as u64);
+
+    let finished = sink.finish().unwrap();
+    assert_eq!(finished.bytes_written, expected().len() as u64);
+    assert_eq!(finished.memory, None);
+}
+
+#[test]
+fn file_sink() {
+    let path = temp_path("list.txt");
+    assert_eq!(list_to::<ChunkedBackend>(&SinkKind::File(path.clone())), None);
+    assert_eq!(fs::read(&path).unwrap(), expected());
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_sink() {
+    let path = temp_path("list.gz");
+    list_to::<ChunkedBackend>(&SinkKind::Gzip(path.clone()));
+
+    let mut decoded = vec![];
+    flate2::read::GzDecoder::new(fs::File::open(&path).unwrap()).read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, expected());
+    assert!(fs::metadata(&path).unwrap().len() < expected().len() as u64);
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_sink() {
+    let path = temp_path("list.zst");
+    list_to::<ChunkedBackend>(&SinkKind::Zstd(path.clone()));
+
+    let mut decoded = vec![];
+    zstd::Decoder::new(fs::File::open(&path).unwrap()).unwrap().read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, expected());
+    assert!(fs::metadata(&path).unwrap().len() < expected().len() as u64);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,84 @@
+//! Checks that the suggestions for an IBAN with a typo hold the intended IBAN,
+//! and only valid ones.
+
+extern crate iban_calculator;
+extern crate proptest;
+
+use proptest::prelude::*;
+
+use iban_calculator::iban::{self, DutchIbans};
+use iban_calculator::iban::suggest::{suggest, Edit};
+use iban_calculator::util;
+
+#[test]
+fn wrong_check_digits() {
+    let suggestions = suggest("NL92ABNA0417164300");
+    assert_eq!(suggestions[0].iban, "NL91ABNA0417164300");
+    assert_eq!(suggestions[0].edit, Edit::Substitution { position: 3, typed: '2', intended: '1' });
+    assert_eq!(suggestions[0].edit.rank(), 2);
+}
+
+#[test]
+fn swapped_digits() {
+    let suggestions = suggest("NL91ABNA0417164030");
+    assert_eq!(suggestions[0].iban, "NL91ABNA0417164300");
+    assert_eq!(suggestions[0].edit, Edit::Transposition { position: 15 });
+    assert_eq!(suggestions[0].edit.rank(), 0);
+}
+
+#[test]
+fn look_alikes_go_first() {
+    // An O typed for a 0 is more likely than the other candidates
+    let suggestions = suggest("DE89370400440532O13000");
+    assert_eq!(suggestions[0].iban, "DE89370400440532013000");
+    assert_eq!(suggestions[0].edit.rank(), 1);
+}
+
+#[test]
+fn ranked() {
+    let suggestions = suggest("DE89370400440532013001");
+    assert!(suggestions.len() > 1);
+    assert!(suggestions.windows(2).all(|w| w[0].edit.rank() <= w[1].edit.rank()));
+}
+
+#[test]
+fn valid_input_is_not_suggested() {
+    assert!(suggest("GB82WEST12345698765432").iter().all(|s| s.iban != "GB82WEST12345698765432"));
+    assert!(suggest("").is_empty());
+}
+
+/// Returns the amount of positions where the strings differ, and whether they
+/// differ by swapping two adjacent characters
+fn distance(a: &str, b: &str) -> (usize, bool) {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let diff: Vec<_> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+    let swapped = diff.len() == 2 && diff[1] == diff[0] + 1 && a[diff[0]] == b[diff[1]] && a[diff[1]] == b[diff[0]];
+    (diff.len(), swapped)
+}
+
+proptest! {
+    #[test]
+    fn typos_are_found(bank in "[A-Z]{4}",
+                       start in 0u32..999_000_000,
+                       position in 0usize..18,
+                       typo in 0usize..36) {
+        // The first account from `start` that passes the elfproef
+        let account = util::MProef::new(11).range(start..u32::MAX).next().unwrap();
+        let intended = DutchIbans::new(&bank).unwrap().iban(account as u64);
+
+        let alphabet = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut typed = intended.clone().into_bytes();
+        typed[position] = alphabet[typo];
+        let typed = String::from_utf8(typed).unwrap();
+        prop_assume!(typed != intended);
+
+        let suggestions = suggest(&typed);
+        prop_assert!(suggestions.iter().any(|s| s.iban == intended));
+        for suggestion in suggestions {
+            prop_assert!(iban::is_valid(&suggestion.iban));
+            prop_assert!(iban::passes_elfproef(&suggestion.iban[8..]));
+            let (differences, swapped) = distance(&typed, &suggestion.iban);
+            prop_assert!(differences == 1 || swapped, "{} {:?}", typed, suggestion);
+        }
+    }
+}
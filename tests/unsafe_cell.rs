@@ -0,0 +1,110 @@
+//! Small tests for every path through which the locks turn their `UnsafeCell`
+//! into a reference, meant to be run under Miri, which reports aliasing
+//! violations and data races that a normal run doesn't notice:
+//!
+//! ```text
+//! cargo +nightly miri test --test unsafe_cell
+//! ```
+//!
+//! Unlike `tests/locks.rs`, they use few threads and iterations, so they finish
+//! in reasonable time in the interpreter.
+
+extern crate iban_calculator;
+
+use std::panic;
+use std::thread;
+use std::time::Duration;
+
+use iban_calculator::lock::{AdvancedSpinLock, RwSpinLock, SpinLock};
+
+const THREADS: usize = 3;
+const ITERATIONS: usize = 20;
+
+#[test]
+fn spin_lock_closure() {
+    let lock = SpinLock::new(vec![]);
+    thread::scope(|scope| {
+        for i in 0..THREADS {
+            let lock = &lock;
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.lock(|v| v.push(i)).unwrap();
+                }
+            });
+        }
+    });
+
+    lock.try_lock(|v| v.push(0)).unwrap();
+    lock.try_lock_for(Duration::from_millis(1), |v| v.push(0)).unwrap();
+    assert_eq!(lock.into_inner().unwrap().len(), THREADS * ITERATIONS + 2);
+}
+
+#[test]
+fn spin_lock_get_mut_after_panic() {
+    let mut lock = SpinLock::new(String::new());
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        lock.lock(|s| { s.push('a'); panic!() }).unwrap();
+    }));
+
+    lock.get_mut().unwrap_err().into_inner().push('b');
+    assert_eq!(lock.into_inner().unwrap_err().into_inner(), "ab");
+}
+
+#[test]
+fn advanced_spin_lock_guards() {
+    let lock = AdvancedSpinLock::new(vec![]);
+    thread::scope(|scope| {
+        for i in 0..THREADS {
+            let lock = &lock;
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    let mut guard = lock.lock().unwrap();
+                    let len = guard.len();
+                    guard.push(i);
+                    assert_eq!(guard.len(), len + 1);
+                }
+            });
+        }
+    });
+
+    lock.try_lock().unwrap().push(0);
+    lock.try_lock_for(Duration::from_millis(1)).unwrap().push(0);
+    assert_eq!(lock.into_inner().unwrap().len(), THREADS * ITERATIONS + 2);
+}
+
+#[test]
+fn advanced_spin_lock_get_mut_after_panic() {
+    let mut lock = AdvancedSpinLock::new(String::new());
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let mut guard = lock.lock().unwrap();
+        guard.push('a');
+        panic!();
+    }));
+
+    lock.get_mut().unwrap_err().into_inner().push('b');
+    assert_eq!(lock.into_inner().unwrap_err().into_inner(), "ab");
+}
+
+#[test]
+fn rw_spin_lock_guards() {
+    let lock = RwSpinLock::new(vec![]);
+    thread::scope(|scope| {
+        for i in 0..THREADS {
+            let lock = &lock;
+            scope.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    lock.write().unwrap().push(i);
+                    let len = lock.read().unwrap().len();
+                    assert!(len > 0);
+
+                    let upgradable = lock.upgradable_read().unwrap();
+                    let len = upgradable.len();
+                    upgradable.upgrade().truncate(len);
+                }
+            });
+        }
+    });
+
+    let reader = lock.write().unwrap().downgrade();
+    assert_eq!(reader.len(), THREADS * ITERATIONS);
+}
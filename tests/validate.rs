@@ -0,0 +1,116 @@
+//! Checks that the validate mode keeps the rows in order on every backend, and
+//! reports why rows failed.
+
+extern crate iban_calculator;
+
+use iban_calculator::backend::*;
+use iban_calculator::iban::DutchIbans;
+use iban_calculator::validate::{self, Column, Format, Kind, Summary, ValidateArgsError, ValidateConfig};
+
+fn config(kind: Kind, format: Format, column: Column) -> ValidateConfig {
+    let header = matches!(column, Column::Name(_));
+    ValidateConfig { kind, format, column, header, threads: 3 }
+}
+
+fn run<T: Backend>(config: &ValidateConfig, input: &str) -> (String, Summary) {
+    let mut out = vec![];
+    let summary = validate::run::<T, _, _>(config, input.as_bytes(), &mut out).unwrap();
+    (String::from_utf8(out).unwrap(), summary)
+}
+
+#[test]
+fn csv_with_header() {
+    let input = "name,iban,amount\n\
+                 Alice,NL91 ABNA 0417 1643 00,10\n\
+                 Bob,NL92ABNA0417164300,\"1,5\"\n\
+                 \"Smith, C\",de89370400440532013000,3\n\
+                 Dan,XX00,1\n\
+                 Eve\n";
+    let (out, summary) = run::<SequentialBackend>(&config(Kind::Iban, Format::Csv, Column::Name("iban".into())), input);
+    assert_eq!(out, "name,iban,amount,valid,reason\n\
+                     Alice,NL91 ABNA 0417 1643 00,10,true,\n\
+                     Bob,NL92ABNA0417164300,\"1,5\",false,wrong check digits\n\
+                     \"Smith, C\",de89370400440532013000,3,true,\n\
+                     Dan,XX00,1,false,unknown country: XX\n\
+                     Eve,false,missing column\n");
+    assert_eq!((summary.rows, summary.valid), (5, 2));
+    assert_eq!(summary.failures["wrong check digits"], 1);
+    assert_eq!(summary.to_string(), "2 of 5 rows are valid\n         1 missing column\n\
+                                     \x20        1 unknown country\n         1 wrong check digits\n");
+}
+
+#[test]
+fn accounts_in_tsv() {
+    // 5000000005 doesn't fit a `u32`
+    let input = "1\t417164300\n2\t417164301\n3\tabc\n4\t5000000005\n";
+    let (out, summary) = run::<RayonBackend>(&config(Kind::Account(11), Format::Tsv, Column::Position(2)), input);
+    assert_eq!(out, "1\t417164300\ttrue\t\n2\t417164301\tfalse\tfails the m_proef\n\
+                     3\tabc\tfalse\tnot an account number\n4\t5000000005\ttrue\t\n");
+    assert_eq!(summary.valid, 2);
+
+    let unknown = validate::run::<SequentialBackend, _, _>(
+        &config(Kind::Iban, Format::Tsv, Column::Name("iban".into())), &b"a\tb\n"[..], vec![]);
+    assert!(matches!(unknown, Err(validate::ValidateError::UnknownColumn(_))));
+}
+
+#[test]
+fn bsns() {
+    let input = "111222333\n10000008\n010000008\n123456789\n000000000\n1234567\n12345678a\n";
+    let (out, summary) = run::<ThreadBackend>(&config(Kind::Bsn, Format::Lines, Column::Position(1)), input);
+    assert_eq!(out, "111222333\ttrue\t\n10000008\ttrue\t\n010000008\ttrue\t\n\
+                     123456789\tfalse\tfails the BSN check\n000000000\tfalse\tfails the BSN check\n1234567\tfalse\tnot a BSN\n12345678a\tfalse\tnot a BSN\n");
+    assert_eq!(summary.failures["not a BSN"], 2);
+}
+
+/// More rows than fit in a batch, with every third one invalid
+fn ibans() -> (String, String) {
+    let ibans = DutchIbans::new("INGB").unwrap();
+    let (mut input, mut expected) = (String::new(), String::new());
+    for i in 0..validate::BATCH_ROWS + 1000 {
+        let iban = ibans.iban(i as u64);
+        let (iban, valid) = if i % 3 == 0 { (iban.replace("NL", "NM"), "false\tunknown country: NM") } else { (iban, "true\t") };
+        input.push_str(&iban);
+        input.push('\n');
+        expected.push_str(&format!("{}\t{}\n", iban, valid));
+    }
+    (input, expected)
+}
+
+#[test]
+fn backends_keep_the_order() {
+    let (input, expected) = ibans();
+    let config = config(Kind::Iban, Format::Lines, Column::Position(1));
+    for &name in BACKEND_NAMES.iter() {
+        struct Check<'a>(&'a ValidateConfig, &'a str);
+        impl<'a> BackendFn for Check<'a> {
+            type Output = (String, Summary);
+            fn call<T: Backend>(self) -> (String, Summary) {
+                run::<T>(self.0, self.1)
+            }
+        }
+
+        let (out, summary) = with_backend(name, Check(&config, &input)).unwrap();
+        assert!(out == expected, "{} changed the output", name);
+        assert_eq!(summary.rows, validate::BATCH_ROWS as u64 + 1000, "{}", name);
+        assert_eq!(summary.failures["unknown country"], summary.rows.div_ceil(3), "{}", name);
+    }
+}
+
+#[test]
+fn args() {
+    let parse = |args: &str| validate::parse_args(args.split_whitespace().map(String::from));
+
+    let config = parse("--kind account --modulo 7 --format tsv --column 3 --header --threads 2").unwrap();
+    assert_eq!((config.kind, config.format, config.column, config.header, config.threads),
+               (Kind::Account(7), Format::Tsv, Column::Position(3), true, 2));
+    let config = parse("--kind iban --column iban").unwrap();
+    assert_eq!((config.kind, config.format, config.column, config.header),
+               (Kind::Iban, Format::Csv, Column::Name("iban".into()), true));
+
+    assert_eq!(parse("--kind bsn").unwrap().kind, Kind::Bsn);
+    assert_eq!(parse("--format csv").err(), Some(ValidateArgsError::MissingKind));
+    assert_eq!(parse("--kind bsn2").err(), Some(ValidateArgsError::InvalidValue("--kind".into(), "bsn2".into())));
+    assert_eq!(parse("--kind iban --column 0").err(), Some(ValidateArgsError::InvalidValue("--column".into(), "0".into())));
+    assert_eq!(parse("--kind iban --column").err(), Some(ValidateArgsError::MissingValue("--column".into())));
+    assert_eq!(parse("--kind iban --bogus 1").err(), Some(ValidateArgsError::UnknownArgument("--bogus".into())));
+}